@@ -2,23 +2,33 @@
 //! 
 //! This module provides high-level coordination of the analysis pipeline.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
 use std::time::Instant;
 
-use log::info;
+use log::{info, warn};
+use tokio::sync::Semaphore;
 
 use crate::config::{defaults, AnalysisSource, Config};
 use crate::contract::contract::Contract;
 use crate::error::{LydiaError, LydiaResult};
 use crate::flow::flow_analysis::FlowAnalysis;
-use crate::graph::call_graph::CallGraph;
+use crate::graph::call_graph::{CallGraph, TraversalLimits};
+use crate::graph::contract_store::ContractStore;
+use crate::graph::model::CallGraphModel;
+use crate::provider::StateProvider;
 use crate::outputter::result_structure::{
-    ExternalCall, OpCreation, Overlap, PathInfo, Result as AnalysisResult, SemanticFeatures,
+    CacheStats, CallCycle, CreatedContract, ExternalCall, OpCreation, Overlap, PathInfo, PhaseFindings, ResolvedProxy,
+    Result as AnalysisResult,
+    SemanticFeatures, Truncation, UnresolvedCall, UnresolvedCallee,
 };
 
 /// Analysis engine that orchestrates the entire analysis pipeline
 pub struct AnalysisEngine {
     config: Config,
+    provider: Arc<dyn StateProvider>,
+    /// Bounds the contracts analyzed at the same time to `config.jobs`
+    workers: Arc<Semaphore>,
 }
 
 /// Holds the results of contract analysis
@@ -26,21 +36,30 @@ pub struct ContractAnalysisResult {
     pub func_sign_list: Vec<String>,
     pub external_call_in_func_signature: HashSet<String>,
     pub is_createbin: bool,
+    pub unresolved_callees: Vec<UnresolvedCallee>,
+    pub unresolved_calls: Vec<UnresolvedCall>,
+    /// Addresses the constructor writes to storage, by slot
+    pub constructor_storage: HashMap<String, String>,
+    pub created_contracts: Vec<CreatedContract>,
+    pub proxy: Option<ResolvedProxy>,
 }
 
 /// Holds the results of call graph analysis
 pub struct CallGraphAnalysisResult {
     pub call_paths: Vec<String>,
+    pub call_graph: CallGraphModel,
+    pub truncation: Truncation,
     pub visited_contracts: HashSet<String>,
     pub visited_funcs: HashSet<String>,
     pub max_call_depth: i32,
-    pub contracts: HashMap<String, Contract>,
+    pub contracts: HashMap<String, Arc<Contract>>,
 }
 
 impl AnalysisEngine {
-    /// Create a new analysis engine with the given configuration
-    pub fn new(config: Config) -> Self {
-        Self { config }
+    /// Create a new analysis engine reading chain state from `provider`
+    pub fn new(config: Config, provider: Arc<dyn StateProvider>) -> Self {
+        let workers = Arc::new(Semaphore::new(config.jobs));
+        Self { config, provider, workers }
     }
 
     /// Run the complete analysis pipeline
@@ -50,40 +69,89 @@ impl AnalysisEngine {
         info!("Starting analysis for contract: {}", self.config.logic_address);
         info!("Platform: {}, Block: {}", self.config.platform, self.config.block_number);
 
+        let mut analysis_result = if self.config.combined {
+            self.analyze_combined().await?
+        } else {
+            self.analyze_phase(false, Arc::default()).await?.1
+        };
+        
+        // Step 4: Finalize results
+        self.finalize_results(&mut analysis_result, start_time);
+
+        Ok(analysis_result)
+    }
+
+    /// Analyze the creation code, then the runtime code resolving empty
+    /// storage slots from the constructor's writes, and merge the findings
+    async fn analyze_combined(&self) -> LydiaResult<AnalysisResult> {
+        let (creation_contract, creation) = self.analyze_phase(true, Arc::default()).await?;
+        if !creation_contract.is_createbin {
+            warn!("No creation bytecode of {}, analyzing its runtime bytecode only", self.config.logic_address);
+            return Ok(self.analyze_phase(false, Arc::default()).await?.1);
+        }
+        let constructor_storage = Arc::new(creation_contract.constructor_storage);
+        info!("Constructor storage writes: {:?}", constructor_storage);
+        let (runtime_contract, runtime) = self.analyze_phase(false, constructor_storage).await?;
+        if runtime_contract.is_createbin {
+            warn!(
+                "{} has no runtime bytecode at block {}, analyzing its creation bytecode only",
+                self.config.logic_address, self.config.block_number
+            );
+            return Ok(creation);
+        }
+        Ok(self.combine_phases(creation, runtime))
+    }
+
+    /// Run the pipeline on the creation code (`createbin`) or the runtime
+    /// code of the input contract
+    async fn analyze_phase(
+        &self,
+        createbin: bool,
+        constructor_storage: Arc<HashMap<String, String>>,
+    ) -> LydiaResult<(ContractAnalysisResult, AnalysisResult)> {
         // Step 1: Analyze the input contract
-        let contract_result = self.analyze_contract().await?;
+        let contract_result = self.analyze_contract(createbin, &constructor_storage).await?;
         
         // Step 2: Build call graphs
-        let call_graph_result = self.build_call_graphs(&contract_result).await?;
+        let call_graph_result = self.build_call_graphs(&contract_result, &constructor_storage).await?;
         
         // Step 3: Perform flow analysis
-        let mut analysis_result = self.perform_flow_analysis(&contract_result, &call_graph_result).await?;
-        
-        // Step 4: Finalize results
-        self.finalize_results(&mut analysis_result, call_graph_result, start_time);
+        let analysis_result = self.perform_flow_analysis(&contract_result, &call_graph_result).await?;
 
-        Ok(analysis_result)
+        Ok((contract_result, analysis_result))
     }
 
     /// Analyze the input contract to extract basic information
-    async fn analyze_contract(&self) -> LydiaResult<ContractAnalysisResult> {
-        let mut contract = Contract::new(
-            self.config.platform.clone(),
-            self.config.logic_address.clone(),
-            self.config.storage_address.clone(),
-            String::new(), // Test all functions
-            self.config.block_number,
-            defaults::CALLER.to_string(),
-            String::new(),
-            defaults::LEVEL,
-        );
+    async fn analyze_contract(
+        &self,
+        createbin: bool,
+        constructor_storage: &Arc<HashMap<String, String>>,
+    ) -> LydiaResult<ContractAnalysisResult> {
+        // An empty function signature tests all functions
+        let source = AnalysisSource {
+            createbin,
+            constructor_storage: constructor_storage.clone(),
+            ..AnalysisSource::from_config(&self.config, self.provider.clone(), String::new())
+        };
+        let mut contract = Contract::from_source(&source);
 
-        contract.analyze().await
-            .map_err(|e| LydiaError::ContractAnalysis(e.to_string()))?;
+        // keep toolchain failures typed, wrap anything else
+        contract.analyze().await.map_err(|e| match e.downcast::<LydiaError>() {
+            Ok(e) => *e,
+            Err(e) => LydiaError::ContractAnalysis(e.to_string()),
+        })?;
 
         let func_sign_list = contract.get_func_sign_list();
         let external_call_in_func_signature = contract.get_external_call_in_func_signature();
         let is_createbin = contract.is_createbin();
+        let constructor_storage = if *is_createbin {
+            contract.constructor_storage_writes().unwrap_or_else(|e| {
+                warn!("Failed to read the constructor's storage writes: {}", e);
+                HashMap::new()
+            })
+        } else {
+            HashMap::new()
+        };
 
         info!("Function signatures found: {:?}", func_sign_list);
         info!("External calls in functions: {:?}", external_call_in_func_signature);
@@ -92,37 +160,80 @@ impl AnalysisEngine {
             func_sign_list: func_sign_list.clone(),
             external_call_in_func_signature: external_call_in_func_signature.clone(),
             is_createbin: *is_createbin,
+            unresolved_callees: contract.unresolved_callees.clone(),
+            unresolved_calls: contract.unresolved_calls(),
+            constructor_storage,
+            created_contracts: contract.created_contracts.clone(),
+            proxy: contract.proxy.clone(),
         })
     }
 
-    /// Build call graphs for the contract
-    async fn build_call_graphs(&self, contract_result: &ContractAnalysisResult) -> LydiaResult<CallGraphAnalysisResult> {
-        let mut contracts = HashMap::new();
+    /// Build call graphs for the contract. The graphs of all functions are
+    /// built concurrently and merged in function signature order.
+    async fn build_call_graphs(
+        &self,
+        contract_result: &ContractAnalysisResult,
+        constructor_storage: &Arc<HashMap<String, String>>,
+    ) -> LydiaResult<CallGraphAnalysisResult> {
+        let store = Arc::new(ContractStore::new());
         let mut visited_contracts = HashSet::new();
         let mut visited_funcs = HashSet::new();
         let mut call_paths = Vec::new();
+        let mut call_graph = CallGraphModel::new();
+        let mut truncation = Truncation {
+            max_depth: self.config.max_depth,
+            max_nodes: self.config.max_nodes,
+            ..Truncation::default()
+        };
         let mut max_call_depth = 0;
 
-        if contract_result.is_createbin {
-            let result = self.build_createbin_call_graph(&mut contracts).await?;
+        let sources: Vec<_> = if contract_result.is_createbin {
+            vec![AnalysisSource::for_createbin(&self.config, self.provider.clone())]
+        } else {
+            // functions deploying a contract of known address have a callee
+            let creating = contract_result
+                .created_contracts
+                .iter()
+                .filter(|created| created.address.is_some())
+                .map(|created| &created.func_sign);
+            let mut func_signs: Vec<_> = contract_result.external_call_in_func_signature.iter().chain(creating).collect();
+            func_signs.sort();
+            func_signs.dedup();
+            func_signs
+                .into_iter()
+                .map(|func_sign| {
+                    info!("Building call flow for function: {}", func_sign);
+                    AnalysisSource::from_config(&self.config, self.provider.clone(), func_sign.to_string())
+                })
+                .collect()
+        };
+        let graphs: Vec<_> = sources
+            .into_iter()
+            .map(|source| AnalysisSource {
+                constructor_storage: constructor_storage.clone(),
+                ..source
+            })
+            .map(|source| tokio::spawn(self.construct_call_graph(source, store.clone())))
+            .collect();
+        for graph in graphs {
+            let result = graph.await.map_err(|e| LydiaError::CallGraphConstruction(e.to_string()))??;
+            println!("{}", result.call_path);
             visited_contracts.extend(result.visited_contracts);
             visited_funcs.extend(result.visited_funcs);
             call_paths.push(result.call_path);
-            max_call_depth = result.max_depth;
-        } else {
-            for func_sign in &contract_result.external_call_in_func_signature {
-                let result = self.build_function_call_graph(func_sign, &mut contracts).await?;
-                visited_contracts.extend(result.visited_contracts);
-                visited_funcs.extend(result.visited_funcs);
-                call_paths.push(result.call_path);
-                max_call_depth = max_call_depth.max(result.max_depth);
-            }
+            call_graph.merge(&result.model);
+            truncation.calls_beyond_depth += result.truncation.calls_beyond_depth;
+            truncation.calls_beyond_budget += result.truncation.calls_beyond_budget;
+            max_call_depth = max_call_depth.max(result.max_depth);
         }
+        let contracts = store.contracts();
 
         info!("Call graph analysis completed. Contracts analyzed: {}", contracts.len());
 
         Ok(CallGraphAnalysisResult {
             call_paths,
+            call_graph,
+            truncation,
             visited_contracts,
             visited_funcs,
             max_call_depth,
@@ -130,35 +241,30 @@ impl AnalysisEngine {
         })
     }
 
-    /// Build call graph for createbin contracts
-    async fn build_createbin_call_graph(&self, contracts: &mut HashMap<String, Contract>) -> LydiaResult<SingleCallGraphResult> {
-        let source = AnalysisSource::for_createbin(&self.config);
-        self.construct_call_graph(source, contracts).await
-    }
-
-    /// Build call graph for a specific function
-    async fn build_function_call_graph(&self, func_sign: &str, contracts: &mut HashMap<String, Contract>) -> LydiaResult<SingleCallGraphResult> {
-        info!("Building call flow for function: {}", func_sign);
-        let source = AnalysisSource::from_config(&self.config, func_sign.to_string());
-        self.construct_call_graph(source, contracts).await
-    }
-
-    /// Construct a call graph from the given source
-    async fn construct_call_graph(&self, source: AnalysisSource, contracts: &mut HashMap<String, Contract>) -> LydiaResult<SingleCallGraphResult> {
-        let mut call_graph = CallGraph::new(self.config.platform.clone(), contracts);
-        
-        call_graph.construct_cross_contract_call_graph(source).await
-            .map_err(|e| LydiaError::CallGraphConstruction(e.to_string()))?;
-
-        let call_path = call_graph.get_output().to_string();
-        println!("{}", call_path);
-
-        Ok(SingleCallGraphResult {
-            call_path,
-            visited_contracts: call_graph.get_visited_contracts().clone(),
-            visited_funcs: call_graph.get_visited_funcs().clone(),
-            max_depth: call_graph.max_level,
-        })
+    /// Construct a call graph from the given source against the shared store
+    fn construct_call_graph(
+        &self,
+        source: AnalysisSource,
+        contracts: Arc<ContractStore>,
+    ) -> impl std::future::Future<Output = LydiaResult<SingleCallGraphResult>> + Send + 'static {
+        let limits = TraversalLimits {
+            max_depth: self.config.max_depth,
+            max_nodes: self.config.max_nodes,
+        };
+        let mut call_graph = CallGraph::new(self.config.platform.clone(), contracts, self.workers.clone(), limits);
+        async move {
+            call_graph.construct_cross_contract_call_graph(source).await
+                .map_err(|e| LydiaError::CallGraphConstruction(e.to_string()))?;
+
+            Ok(SingleCallGraphResult {
+                call_path: call_graph.get_output().to_string(),
+                visited_contracts: call_graph.get_visited_contracts().clone(),
+                visited_funcs: call_graph.get_visited_funcs().clone(),
+                max_depth: call_graph.max_level,
+                model: call_graph.get_model().clone(),
+                truncation: call_graph.get_truncation().clone(),
+            })
+        }
     }
 
     /// Perform flow analysis to detect malicious patterns
@@ -169,9 +275,16 @@ impl AnalysisEngine {
             contract_result.external_call_in_func_signature.clone(),
             call_graph_result.visited_contracts.clone(),
             call_graph_result.visited_funcs.clone(),
+            self.config.workspace.clone(),
         );
 
-        let (is_attack, attack_matrix) = detector.detect();
+        let (mut is_attack, mut attack_matrix) = detector.detect();
+        let cycles = Self::collect_cycles(&call_graph_result.call_graph);
+        // calls returning into the analyzed contract are a reentrancy signal
+        let call_graph = &call_graph_result.call_graph;
+        let reenters_entry = call_graph.cycles.iter().any(|cycle| call_graph.roots.contains(&cycle[0]));
+        attack_matrix.insert("cycle".to_string(), reenters_entry);
+        is_attack |= reenters_entry;
 
         let mut result = AnalysisResult {
             is_attack,
@@ -184,6 +297,7 @@ impl AnalysisEngine {
             }.to_string(),
             platform: self.config.platform.clone(),
             block_number: self.config.block_number,
+            tx_context: self.config.tx.clone(),
             time: None,
             semantic_features: SemanticFeatures {
                 op_creation: OpCreation {
@@ -198,6 +312,9 @@ impl AnalysisEngine {
                 externalcall_infallback: detector.externalcall_infallback(),
             },
             call_paths: call_graph_result.call_paths.clone(),
+            call_graph: call_graph_result.call_graph.clone(),
+            cycles,
+            truncation: call_graph_result.truncation.clone(),
             visited_contracts: call_graph_result.visited_contracts.iter().cloned().collect(),
             visited_contracts_num: call_graph_result.visited_contracts.len(),
             visited_funcs: call_graph_result.visited_funcs.iter().cloned().collect(),
@@ -211,6 +328,12 @@ impl AnalysisEngine {
                 overlap_external_call: Vec::new(),
             },
             reentrancy_path_info: HashMap::new(),
+            unresolved_callees: self.collect_unresolved_callees(contract_result, call_graph_result),
+            unresolved_calls: self.collect_unresolved_calls(contract_result, call_graph_result),
+            decompile_cache: CacheStats::default(),
+            created_contracts: Self::collect_created_contracts(contract_result, call_graph_result, &detector),
+            proxies: Self::collect_proxies(contract_result, call_graph_result),
+            phases: Vec::new(),
         };
 
         // Analyze reentrancy paths
@@ -241,6 +364,88 @@ impl AnalysisEngine {
         Ok(result)
     }
 
+    /// Spell out the call cycles found in the call graph
+    fn collect_cycles(call_graph: &CallGraphModel) -> Vec<CallCycle> {
+        call_graph
+            .cycles
+            .iter()
+            .map(|cycle| CallCycle {
+                path: cycle.iter().map(|&node| call_graph.nodes[node].clone()).collect(),
+            })
+            .collect()
+    }
+
+    /// Gather the contracts created by every analyzed contract, with the
+    /// features of the constructors followed in the call graph
+    fn collect_created_contracts(
+        contract_result: &ContractAnalysisResult,
+        call_graph_result: &CallGraphAnalysisResult,
+        detector: &FlowAnalysis,
+    ) -> Vec<CreatedContract> {
+        let mut created: Vec<CreatedContract> = contract_result
+            .created_contracts
+            .iter()
+            .chain(call_graph_result.contracts.values().flat_map(|contract| contract.created_contracts.iter()))
+            .cloned()
+            .collect();
+        created.sort_by(|a, b| (&a.creator, &a.create_site).cmp(&(&b.creator, &b.create_site)));
+        created.dedup_by(|a, b| a.creator == b.creator && a.create_site == b.create_site);
+        for contract in &mut created {
+            if let Some(address) = &contract.address {
+                if call_graph_result.visited_contracts.contains(address) {
+                    contract.semantic_features =
+                        Some(detector.semantic_features_of(address, defaults::CREATEBIN_FUNC_SELECTOR));
+                }
+            }
+        }
+        created
+    }
+
+    /// Gather the standard proxies among the analyzed contracts
+    fn collect_proxies(contract_result: &ContractAnalysisResult, call_graph_result: &CallGraphAnalysisResult) -> Vec<ResolvedProxy> {
+        let mut proxies: Vec<ResolvedProxy> = contract_result
+            .proxy
+            .iter()
+            .chain(call_graph_result.contracts.values().filter_map(|contract| contract.proxy.as_ref()))
+            .cloned()
+            .collect();
+        proxies.sort();
+        proxies.dedup();
+        proxies
+    }
+
+    /// Gather the callees every analyzed contract failed to resolve, without duplicates
+    fn collect_unresolved_callees(&self, contract_result: &ContractAnalysisResult, call_graph_result: &CallGraphAnalysisResult) -> Vec<UnresolvedCallee> {
+        let mut unresolved: Vec<UnresolvedCallee> = contract_result
+            .unresolved_callees
+            .iter()
+            .chain(call_graph_result.contracts.values().flat_map(|contract| contract.unresolved_callees.iter()))
+            .cloned()
+            .collect();
+        unresolved.sort();
+        unresolved.dedup();
+        unresolved
+    }
+
+    /// Gather the external calls each analyzed contract could not resolve, by contract address
+    fn collect_unresolved_calls(&self, contract_result: &ContractAnalysisResult, call_graph_result: &CallGraphAnalysisResult) -> BTreeMap<String, Vec<UnresolvedCall>> {
+        let mut unresolved: BTreeMap<String, Vec<UnresolvedCall>> = BTreeMap::new();
+        if !contract_result.unresolved_calls.is_empty() {
+            unresolved.insert(self.config.logic_address.to_lowercase(), contract_result.unresolved_calls.clone());
+        }
+        for contract in call_graph_result.contracts.values() {
+            let calls = contract.unresolved_calls();
+            if !calls.is_empty() {
+                unresolved.entry(contract.logic_addr.to_lowercase()).or_default().extend(calls);
+            }
+        }
+        for calls in unresolved.values_mut() {
+            calls.sort();
+            calls.dedup();
+        }
+        unresolved
+    }
+
     /// Calculate the appropriate warning level based on analysis results
     fn calculate_warning_level(&self, result: &mut AnalysisResult) {
         if result.semantic_features.op_creation.op_multicreate
@@ -250,13 +455,101 @@ impl AnalysisEngine {
             || result.overlap.has_overlap
             || result.external_call.externalcall_inhook
             || result.external_call.externalcall_infallback
+            || !result.cycles.is_empty()
         {
             result.warning = defaults::WARNING_HIGH.to_string();
         }
     }
 
+    /// Merge the results of the creation and the runtime phase, keeping the
+    /// findings of each phase in `phases`
+    fn combine_phases(&self, creation: AnalysisResult, runtime: AnalysisResult) -> AnalysisResult {
+        let phases = vec![PhaseFindings::of(&creation), PhaseFindings::of(&runtime)];
+        let mut result = runtime;
+        result.analysis_loc = defaults::COMBINED_ANALYSIS_LOC.to_string();
+        result.is_attack |= creation.is_attack;
+        for (name, hit) in creation.attack_matrix {
+            *result.attack_matrix.entry(name).or_default() |= hit;
+        }
+
+        let features = &mut result.semantic_features;
+        features.op_creation.op_multicreate |= creation.semantic_features.op_creation.op_multicreate;
+        features.op_creation.op_solecreate |= creation.semantic_features.op_creation.op_solecreate;
+        features.op_selfdestruct |= creation.semantic_features.op_selfdestruct;
+        features.op_env |= creation.semantic_features.op_env;
+        result.external_call.externalcall_inhook |= creation.external_call.externalcall_inhook;
+        result.external_call.externalcall_infallback |= creation.external_call.externalcall_infallback;
+
+        // the constructor runs first
+        result.call_paths = creation.call_paths.into_iter().chain(result.call_paths).collect();
+        let mut call_graph = creation.call_graph;
+        call_graph.merge(&result.call_graph);
+        result.call_graph = call_graph;
+        result.cycles = creation.cycles.into_iter().chain(result.cycles).collect();
+        result.truncation.calls_beyond_depth += creation.truncation.calls_beyond_depth;
+        result.truncation.calls_beyond_budget += creation.truncation.calls_beyond_budget;
+        result.max_call_depth = result.max_call_depth.max(creation.max_call_depth);
+
+        let union = |first: Vec<String>, second: &[String]| -> Vec<String> {
+            let mut merged = first;
+            for item in second {
+                if !merged.contains(item) {
+                    merged.push(item.clone());
+                }
+            }
+            merged
+        };
+        result.visited_contracts = union(creation.visited_contracts, &result.visited_contracts);
+        result.visited_contracts_num = result.visited_contracts.len();
+        result.visited_funcs = union(creation.visited_funcs, &result.visited_funcs);
+        result.visited_funcs_num = result.visited_funcs.len();
+        result.contract_funcsigs = union(creation.contract_funcsigs, &result.contract_funcsigs);
+        result.contract_funcsigs_external_call =
+            union(creation.contract_funcsigs_external_call, &result.contract_funcsigs_external_call);
+        result.sensitive_callsigs = union(creation.sensitive_callsigs, &result.sensitive_callsigs);
+        result.overlap.has_overlap |= creation.overlap.has_overlap;
+        result.overlap.overlap_external_call =
+            union(creation.overlap.overlap_external_call, &result.overlap.overlap_external_call);
+        result.reentrancy_path_info.extend(creation.reentrancy_path_info);
+
+        result.unresolved_callees.extend(creation.unresolved_callees);
+        result.unresolved_callees.sort();
+        result.unresolved_callees.dedup();
+        for (address, calls) in creation.unresolved_calls {
+            let merged = result.unresolved_calls.entry(address).or_default();
+            merged.extend(calls);
+            merged.sort();
+            merged.dedup();
+        }
+
+        for created in creation.created_contracts {
+            let known = result
+                .created_contracts
+                .iter()
+                .any(|other| other.creator == created.creator && other.create_site == created.create_site);
+            if !known {
+                result.created_contracts.push(created);
+            }
+        }
+
+        result.proxies.extend(creation.proxies);
+        result.proxies.sort();
+        result.proxies.dedup();
+
+        result.warning = defaults::WARNING_MEDIUM.to_string();
+        self.calculate_warning_level(&mut result);
+        result.phases = phases;
+        result
+    }
+
     /// Finalize the analysis results with timing and output
-    fn finalize_results(&self, result: &mut AnalysisResult, _call_graph_result: CallGraphAnalysisResult, start_time: Instant) {
+    fn finalize_results(&self, result: &mut AnalysisResult, start_time: Instant) {
+        if let Err(e) = self.config.decompile_cache.evict(&self.config.workspace) {
+            warn!("Failed to evict decompiled codes: {}", e);
+        }
+        result.decompile_cache = self.config.decompile_cache.stats();
+        info!("Decompile cache: {:?}", result.decompile_cache);
+
         let duration = start_time.elapsed();
         result.time = Some(format!("{}.{:09} seconds", duration.as_secs(), duration.subsec_nanos()));
         
@@ -270,4 +563,6 @@ struct SingleCallGraphResult {
     visited_contracts: HashSet<String>,
     visited_funcs: HashSet<String>,
     max_depth: i32,
+    model: CallGraphModel,
+    truncation: Truncation,
 }
\ No newline at end of file
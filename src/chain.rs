@@ -0,0 +1,257 @@
+//! Chain registry for Lydia
+//!
+//! This module maps blockchain platforms (e.g. `ETH`, `BSC`) to the RPC
+//! endpoints used to fetch bytecode and storage. The registry is assembled
+//! from built-in chain ids, an optional TOML/JSON registry file and
+//! `LYDIA_<PLATFORM>_*` environment variables, in increasing precedence.
+//! No endpoint is built in: every platform analyzed online needs one from
+//! the registry file or the environment.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfigError;
+
+/// Environment variables understood by the registry
+pub mod env {
+    /// Path of the registry file, used when `--chain_config` is not given
+    pub const CHAIN_CONFIG: &str = "LYDIA_CHAIN_CONFIG";
+    pub const PREFIX: &str = "LYDIA_";
+    /// `LYDIA_<PLATFORM>_RPC_URL`: primary HTTP(S) or WS(S) endpoint
+    pub const RPC_URL_SUFFIX: &str = "_RPC_URL";
+    /// `LYDIA_<PLATFORM>_FALLBACK_URLS`: comma-separated fallback endpoints
+    pub const FALLBACK_URLS_SUFFIX: &str = "_FALLBACK_URLS";
+    /// `LYDIA_<PLATFORM>_CHAIN_ID`: numeric chain id
+    pub const CHAIN_ID_SUFFIX: &str = "_CHAIN_ID";
+}
+
+/// Connection settings of a single blockchain platform
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainConfig {
+    #[serde(default)]
+    pub chain_id: u64,
+    #[serde(default)]
+    pub rpc_url: String,
+    #[serde(default)]
+    pub fallback_urls: Vec<String>,
+}
+
+impl ChainConfig {
+    /// All configured endpoints, primary first, then fallbacks in order
+    pub fn endpoints(&self) -> Vec<&str> {
+        std::iter::once(self.rpc_url.as_str())
+            .chain(self.fallback_urls.iter().map(String::as_str))
+            .filter(|url| !url.is_empty())
+            .collect()
+    }
+
+    pub fn has_endpoint(&self) -> bool {
+        !self.endpoints().is_empty()
+    }
+}
+
+/// Registry of all known blockchain platforms, keyed by upper-case name
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainRegistry {
+    #[serde(default)]
+    chains: HashMap<String, ChainConfig>,
+}
+
+impl ChainRegistry {
+    /// Registry holding the chain ids of the platforms Lydia knows, without
+    /// any endpoint
+    pub fn builtin() -> Self {
+        let mut registry = Self::default();
+        for (platform, chain_id) in [("ETH", 1), ("BSC", 56)] {
+            registry.insert(
+                platform,
+                ChainConfig {
+                    chain_id,
+                    ..ChainConfig::default()
+                },
+            );
+        }
+        registry
+    }
+
+    /// Build the registry used by a run: built-in chain ids, then the registry
+    /// file (if any), then environment overrides
+    pub fn load(path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut registry = Self::builtin();
+        if let Some(path) = path {
+            registry.merge(Self::from_file(path)?);
+        }
+        registry.apply_env_overrides(std::env::vars())?;
+        Ok(registry)
+    }
+
+    /// Parse a registry file; `.toml` files are read as TOML, anything else as JSON
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            ConfigError::ChainRegistry(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        let is_toml = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("toml"))
+            .unwrap_or(false);
+        if is_toml {
+            Self::from_toml_str(&content)
+        } else {
+            Self::from_json_str(&content)
+        }
+    }
+
+    pub fn from_toml_str(content: &str) -> Result<Self, ConfigError> {
+        let parsed: Self =
+            toml::from_str(content).map_err(|e| ConfigError::ChainRegistry(e.to_string()))?;
+        Ok(parsed.normalized())
+    }
+
+    pub fn from_json_str(content: &str) -> Result<Self, ConfigError> {
+        let parsed: Self =
+            serde_json::from_str(content).map_err(|e| ConfigError::ChainRegistry(e.to_string()))?;
+        Ok(parsed.normalized())
+    }
+
+    /// Entries of `other` replace entries of the same platform in `self`
+    pub fn merge(&mut self, other: ChainRegistry) {
+        self.chains.extend(other.chains);
+    }
+
+    /// Apply `LYDIA_<PLATFORM>_{RPC_URL,FALLBACK_URLS,CHAIN_ID}` overrides.
+    /// Unknown platforms are added to the registry.
+    pub fn apply_env_overrides<I>(&mut self, vars: I) -> Result<(), ConfigError>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (key, value) in vars {
+            let Some(rest) = key.strip_prefix(env::PREFIX) else {
+                continue;
+            };
+            if let Some(platform) = rest.strip_suffix(env::RPC_URL_SUFFIX) {
+                self.entry(platform).rpc_url = value.trim().to_string();
+            } else if let Some(platform) = rest.strip_suffix(env::FALLBACK_URLS_SUFFIX) {
+                self.entry(platform).fallback_urls = value
+                    .split(',')
+                    .map(|url| url.trim().to_string())
+                    .filter(|url| !url.is_empty())
+                    .collect();
+            } else if let Some(platform) = rest.strip_suffix(env::CHAIN_ID_SUFFIX) {
+                let chain_id = value
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|e| ConfigError::ChainRegistry(format!("{}: {}", key, e)))?;
+                self.entry(platform).chain_id = chain_id;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, platform: &str) -> Option<&ChainConfig> {
+        self.chains.get(&platform.to_uppercase())
+    }
+
+    pub fn insert(&mut self, platform: &str, chain: ChainConfig) {
+        self.chains.insert(platform.to_uppercase(), chain);
+    }
+
+    pub fn platforms(&self) -> Vec<&str> {
+        let mut platforms: Vec<&str> = self.chains.keys().map(String::as_str).collect();
+        platforms.sort_unstable();
+        platforms
+    }
+
+    fn entry(&mut self, platform: &str) -> &mut ChainConfig {
+        self.chains.entry(platform.to_uppercase()).or_default()
+    }
+
+    fn normalized(self) -> Self {
+        let mut registry = Self::default();
+        for (platform, chain) in self.chains {
+            registry.insert(&platform, chain);
+        }
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_registry() {
+        let registry = ChainRegistry::from_toml_str(
+            r#"
+            [chains.eth]
+            chain_id = 1
+            rpc_url = "http://localhost:8545"
+            fallback_urls = ["wss://archive.example/ws"]
+            "#,
+        )
+        .unwrap();
+        let chain = registry.get("ETH").unwrap();
+        assert_eq!(chain.chain_id, 1);
+        assert_eq!(
+            chain.endpoints(),
+            vec!["http://localhost:8545", "wss://archive.example/ws"]
+        );
+    }
+
+    #[test]
+    fn test_json_registry_merges_over_builtin() {
+        let mut registry = ChainRegistry::builtin();
+        registry.merge(
+            ChainRegistry::from_json_str(
+                r#"{"chains": {"BSC": {"chain_id": 56, "rpc_url": "http://127.0.0.1:8545"}}}"#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            registry.get("bsc").unwrap().rpc_url,
+            "http://127.0.0.1:8545"
+        );
+        // built-in platforms come without endpoints
+        let eth = registry.get("ETH").unwrap();
+        assert_eq!(eth.chain_id, 1);
+        assert!(!eth.has_endpoint());
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut registry = ChainRegistry::builtin();
+        registry
+            .apply_env_overrides(vec![
+                (
+                    "LYDIA_ETH_RPC_URL".to_string(),
+                    "http://node:8545".to_string(),
+                ),
+                (
+                    "LYDIA_ARB_FALLBACK_URLS".to_string(),
+                    "https://a.example, https://b.example".to_string(),
+                ),
+                ("LYDIA_ARB_CHAIN_ID".to_string(), "42161".to_string()),
+                ("LYDIA_CHAIN_CONFIG".to_string(), "ignored.toml".to_string()),
+                ("PATH".to_string(), "/usr/bin".to_string()),
+            ])
+            .unwrap();
+        assert_eq!(registry.get("ETH").unwrap().rpc_url, "http://node:8545");
+        let arb = registry.get("ARB").unwrap();
+        assert_eq!(arb.chain_id, 42161);
+        assert_eq!(
+            arb.endpoints(),
+            vec!["https://a.example", "https://b.example"]
+        );
+        assert_eq!(registry.platforms(), vec!["ARB", "BSC", "ETH"]);
+    }
+
+    #[test]
+    fn test_invalid_chain_id() {
+        let mut registry = ChainRegistry::default();
+        let result = registry
+            .apply_env_overrides(vec![("LYDIA_ETH_CHAIN_ID".to_string(), "one".to_string())]);
+        assert!(result.is_err());
+    }
+}
@@ -3,8 +3,25 @@
 //! This module handles command-line argument parsing and application configuration.
 
 use clap::{App, Arg};
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use web3::types::{H256, U256};
+
+use crate::chain::{self, ChainConfig, ChainRegistry};
+use crate::contract::bytecode::{decode_hex, synthetic_address};
+use crate::contract::decompile_cache::DecompileCache;
+use crate::contract::deployment::DeploymentIndex;
+use crate::contract::gigahorse::GigahorseRunner;
+use crate::outputter::graph_renderer::GraphFormat;
+use crate::outputter::result_structure::TxContext;
+use crate::utils::is_valid_ethereum_address;
+use crate::workspace::{self, Workspace};
+use crate::provider::StateProvider;
 
 /// Default values used throughout the application
 pub mod defaults {
@@ -18,6 +35,13 @@ pub mod defaults {
     pub const CREATEBIN_FUNC_SELECTOR: &str = "__function_selector__";
     pub const CREATEBIN_ANALYSIS_LOC: &str = "createbin";
     pub const RUNTIMEBIN_ANALYSIS_LOC: &str = "runtimebin";
+    pub const COMBINED_ANALYSIS_LOC: &str = "createbin+runtimebin";
+    pub const GIGAHORSE_CLIENT: &str = "./clients/leslie.dl";
+    pub const DECOMPILE_TIMEOUT_SECS: u64 = 120;
+    pub const DECOMPILE_CACHE_MB: u64 = 2048;
+    pub const JOBS: usize = 4;
+    pub const MAX_DEPTH: i32 = 16;
+    pub const MAX_NODES: usize = 2000;
 }
 
 /// Configuration structure holding all application settings
@@ -27,6 +51,36 @@ pub struct Config {
     pub logic_address: String,
     pub storage_address: String,
     pub block_number: u64,
+    pub chains: ChainRegistry,
+    /// Read state from a local fixture instead of an RPC endpoint
+    pub offline: bool,
+    /// Bytecode given with `--bytecode`, analyzed at `logic_address`
+    pub bytecode: Option<Vec<u8>>,
+    /// Treat `bytecode` as creation code instead of detecting it
+    pub createbin: bool,
+    /// Analyze both the creation and the runtime code of the contract
+    pub combined: bool,
+    pub storage_snapshot: Option<PathBuf>,
+    pub workspace: Workspace,
+    /// Keep the per-run scratch directory after the analysis
+    pub keep_temp: bool,
+    pub gigahorse: GigahorseRunner,
+    pub decompile_cache: Arc<DecompileCache>,
+    /// Deployment transactions to take creation code from
+    pub deployments: Arc<DeploymentIndex>,
+    /// Number of contracts analyzed concurrently
+    pub jobs: usize,
+    /// Also save the call graph in this format
+    pub graph_format: Option<GraphFormat>,
+    /// Deepest call level the call graph follows
+    pub max_depth: i32,
+    /// Nodes of one function's call graph after which calls are not followed
+    pub max_nodes: usize,
+    /// Origin, sender and value of the analyzed transaction
+    pub tx: TxContext,
+    /// Transaction given with `lydia tx <hash>`, whose target is analyzed
+    /// once fetched
+    pub tx_hash: Option<H256>,
 }
 
 /// Custom error type for configuration-related errors
@@ -35,6 +89,9 @@ pub enum ConfigError {
     InvalidBlockNumber(String),
     MissingRequiredField(String),
     ParseError(String),
+    ChainRegistry(String),
+    UnknownPlatform(String),
+    OfflineInput(String),
 }
 
 impl fmt::Display for ConfigError {
@@ -43,6 +100,15 @@ impl fmt::Display for ConfigError {
             ConfigError::InvalidBlockNumber(msg) => write!(f, "Invalid block number: {}", msg),
             ConfigError::MissingRequiredField(field) => write!(f, "Missing required field: {}", field),
             ConfigError::ParseError(msg) => write!(f, "Parse error: {}", msg),
+            ConfigError::ChainRegistry(msg) => write!(f, "Chain registry error: {}", msg),
+            ConfigError::UnknownPlatform(platform) => write!(
+                f,
+                "No RPC endpoint configured for platform: {}; pass a chain registry with \
+                 -c/--chain_config (or LYDIA_CHAIN_CONFIG) or set LYDIA_{}_RPC_URL",
+                platform,
+                platform.to_uppercase()
+            ),
+            ConfigError::OfflineInput(msg) => write!(f, "Offline input error: {}", msg),
         }
     }
 }
@@ -56,6 +122,17 @@ impl Config {
             .version("1.0")
             .author("Shuo Yang <yangsh233@mail2.sysu.edu.cn>")
             .about("Finding Attacker Contracts with Malicious Intents")
+            .subcommand_negates_reqs(true)
+            .subcommand(
+                App::new("tx")
+                    .about("Analyze the contract deployed or called by a transaction")
+                    .arg(
+                        Arg::with_name("hash")
+                            .value_name("TX_HASH")
+                            .help("Hash of the transaction")
+                            .required(true),
+                    ),
+            )
             .arg(
                 Arg::with_name("blockchain_platform")
                     .short('b')
@@ -72,7 +149,7 @@ impl Config {
                     .value_name("LOGIC_ADDR")
                     .help("Contract address for storing business logic")
                     .takes_value(true)
-                    .required(true),
+                    .required_unless_present("bytecode"),
             )
             .arg(
                 Arg::with_name("storage_address")
@@ -91,6 +168,177 @@ impl Config {
                     .takes_value(true)
                     .default_value(&defaults::BLOCK_NUMBER.to_string()),
             )
+            .arg(
+                Arg::with_name("origin")
+                    .long("origin")
+                    .value_name("ADDR")
+                    .help("EOA sending the transaction (tx.origin), defaults to --sender")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("sender")
+                    .long("sender")
+                    .value_name("ADDR")
+                    .help("Caller of the analyzed function (msg.sender), defaults to --origin")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("value")
+                    .long("value")
+                    .value_name("WEI")
+                    .help("Wei sent with the call, in decimal or 0x-prefixed hex")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("chain_config")
+                    .short('c')
+                    .long("chain_config")
+                    .value_name("FILE")
+                    .help("TOML/JSON chain registry with RPC endpoints per platform")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("offline")
+                    .long("offline")
+                    .help("Never query an RPC endpoint; unresolved callees are reported instead"),
+            )
+            .arg(
+                Arg::with_name("bytecode")
+                    .long("bytecode")
+                    .value_name("FILE")
+                    .help("Analyze a runtime or creation bytecode hex file offline")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("createbin")
+                    .long("createbin")
+                    .help("Treat the --bytecode file as creation bytecode instead of detecting it")
+                    .requires("bytecode"),
+            )
+            .arg(
+                Arg::with_name("combined")
+                    .long("combined")
+                    .help("Analyze both the creation and the runtime bytecode, reporting findings per phase"),
+            )
+            .arg(
+                Arg::with_name("storage_snapshot")
+                    .long("storage_snapshot")
+                    .value_name("FILE")
+                    .help("JSON code/storage snapshot used to resolve callees offline")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("deployment_index")
+                    .long("deployment_index")
+                    .value_name("FILE")
+                    .help("JSON map of contract addresses to their deployment tx hashes")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("deploy_tx")
+                    .long("deploy_tx")
+                    .value_name("TX_HASH")
+                    .help("Deployment transaction of the analyzed contract")
+                    .takes_value(true)
+                    .requires("logic_address"),
+            )
+            .arg(
+                Arg::with_name("gigahorse_root")
+                    .long("gigahorse_root")
+                    .value_name("DIR")
+                    .help("Root directory of the Gigahorse toolchain [default: ./gigahorse-toolchain]")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("gigahorse_client")
+                    .long("gigahorse_client")
+                    .value_name("FILE")
+                    .help("Datalog client run after decompilation, relative to the toolchain root")
+                    .takes_value(true)
+                    .default_value(defaults::GIGAHORSE_CLIENT),
+            )
+            .arg(
+                Arg::with_name("contracts_dir")
+                    .long("contracts_dir")
+                    .value_name("DIR")
+                    .help("Directory for bytecode files [default: <gigahorse_root>/contracts]")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("temp_dir")
+                    .long("temp_dir")
+                    .value_name("DIR")
+                    .help("Gigahorse working directory [default: <gigahorse_root>/.temp]")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("keep_temp")
+                    .long("keep_temp")
+                    .help("Keep this run's scratch directory under the temp dir for inspection"),
+            )
+            .arg(
+                Arg::with_name("cache_dir")
+                    .long("cache_dir")
+                    .value_name("DIR")
+                    .help("Cache of compiled Datalog clients [default: <gigahorse_root>/cache]")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("decompile_cache")
+                    .long("decompile_cache")
+                    .value_name("DIR")
+                    .help("Persistent cache of decompiled codes [default: <temp_dir>/decompiled]")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::with_name("decompile_cache_mb")
+                    .long("decompile_cache_mb")
+                    .value_name("MB")
+                    .help("Size above which least recently used decompiled codes are evicted")
+                    .takes_value(true)
+                    .default_value(&defaults::DECOMPILE_CACHE_MB.to_string()),
+            )
+            .arg(
+                Arg::with_name("jobs")
+                    .long("jobs")
+                    .short('j')
+                    .value_name("N")
+                    .help("Number of contracts analyzed concurrently")
+                    .takes_value(true)
+                    .default_value(&defaults::JOBS.to_string()),
+            )
+            .arg(
+                Arg::with_name("max_depth")
+                    .long("max_depth")
+                    .value_name("LEVEL")
+                    .help("Deepest call level followed in the call graph")
+                    .takes_value(true)
+                    .default_value(&defaults::MAX_DEPTH.to_string()),
+            )
+            .arg(
+                Arg::with_name("max_nodes")
+                    .long("max_nodes")
+                    .value_name("N")
+                    .help("Node budget of the call graph of each function")
+                    .takes_value(true)
+                    .default_value(&defaults::MAX_NODES.to_string()),
+            )
+            .arg(
+                Arg::with_name("graph_format")
+                    .long("graph_format")
+                    .value_name("FORMAT")
+                    .help("Also save the call graph as dot, mermaid or json")
+                    .takes_value(true)
+                    .possible_values(GraphFormat::NAMES),
+            )
+            .arg(
+                Arg::with_name("decompile_timeout")
+                    .long("decompile_timeout")
+                    .value_name("SECS")
+                    .help("Time budget for decompiling and analyzing each contract")
+                    .takes_value(true)
+                    .default_value(&defaults::DECOMPILE_TIMEOUT_SECS.to_string()),
+            )
             .get_matches();
 
         let platform = matches
@@ -98,10 +346,25 @@ impl Config {
             .ok_or_else(|| ConfigError::MissingRequiredField("blockchain_platform".to_string()))?
             .to_string();
 
-        let logic_address = matches
-            .value_of("logic_address")
-            .ok_or_else(|| ConfigError::MissingRequiredField("logic_address".to_string()))?
-            .to_string();
+        let bytecode = match matches.value_of("bytecode") {
+            Some(path) => Some(Self::read_bytecode(Path::new(path))?),
+            None => None,
+        };
+
+        let tx_hash = match matches.subcommand_matches("tx") {
+            Some(tx) => Some(Self::parse_tx_hash(tx.value_of("hash").unwrap_or_default())?),
+            None => None,
+        };
+
+        // in transaction mode the address is only known once the transaction is fetched
+        let logic_address = match (matches.value_of("logic_address"), &bytecode) {
+            (Some(addr), _) => addr.to_string(),
+            (None, Some(code)) => format!("{:?}", synthetic_address(code)),
+            (None, None) if tx_hash.is_some() => String::new(),
+            (None, None) => {
+                return Err(ConfigError::MissingRequiredField("logic_address".to_string()))
+            }
+        };
 
         let storage_address = matches
             .value_of("storage_address")
@@ -114,26 +377,200 @@ impl Config {
             .parse::<u64>()
             .map_err(|e| ConfigError::InvalidBlockNumber(e.to_string()))?;
 
+        let origin = matches
+            .value_of("origin")
+            .map(|addr| Self::parse_tx_address("origin", addr))
+            .transpose()?;
+        let sender = matches
+            .value_of("sender")
+            .map(|addr| Self::parse_tx_address("sender", addr))
+            .transpose()?;
+        let value = matches.value_of("value").map(Self::parse_value).transpose()?;
+        // a transaction sent by an EOA has it as both origin and sender
+        let tx = TxContext {
+            origin: origin.clone().or_else(|| sender.clone()),
+            sender: sender.or(origin),
+            value,
+        };
+
+        let chain_config = matches
+            .value_of("chain_config")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os(chain::env::CHAIN_CONFIG).map(PathBuf::from));
+        let chains = ChainRegistry::load(chain_config.as_deref())?;
+
+        let mut deployments = match matches.value_of("deployment_index") {
+            Some(path) => DeploymentIndex::from_file(Path::new(path))?,
+            None => DeploymentIndex::new(),
+        };
+        if let Some(hash) = matches.value_of("deploy_tx") {
+            let addr = logic_address
+                .parse()
+                .map_err(|_| ConfigError::ParseError("Invalid Ethereum address format".to_string()))?;
+            deployments.insert(addr, Self::parse_tx_hash(hash)?);
+        }
+
+        let storage_snapshot = matches.value_of("storage_snapshot").map(PathBuf::from);
+        let offline = matches.is_present("offline") || storage_snapshot.is_some() || bytecode.is_some();
+
+        let decompile_timeout = matches
+            .value_of("decompile_timeout")
+            .ok_or_else(|| ConfigError::MissingRequiredField("decompile_timeout".to_string()))?
+            .parse::<u64>()
+            .map_err(|e| ConfigError::ParseError(format!("decompile_timeout: {}", e)))?;
+        let gigahorse = GigahorseRunner::new(
+            PathBuf::from(matches.value_of("gigahorse_client").unwrap_or(defaults::GIGAHORSE_CLIENT)),
+            Duration::from_secs(decompile_timeout),
+        );
+
+        // command line first, then environment, then the toolchain layout
+        let path_arg = |name: &str, var: &str| {
+            matches
+                .value_of(name)
+                .map(PathBuf::from)
+                .or_else(|| std::env::var_os(var).map(PathBuf::from))
+        };
+        let mut workspace = Workspace::new(
+            path_arg("gigahorse_root", workspace::env::TOOLCHAIN_DIR)
+                .unwrap_or_else(|| PathBuf::from(workspace::DEFAULT_TOOLCHAIN_DIR)),
+        );
+        if let Some(dir) = path_arg("contracts_dir", workspace::env::CONTRACTS_DIR) {
+            workspace.contracts_dir = dir;
+        }
+        if let Some(dir) = path_arg("temp_dir", workspace::env::TEMP_DIR) {
+            workspace.scratch_dir = dir.clone();
+            workspace.decompiled_dir = dir.join("decompiled");
+            workspace.temp_dir = dir;
+        }
+        if let Some(dir) = path_arg("decompile_cache", workspace::env::DECOMPILED_DIR) {
+            workspace.decompiled_dir = dir;
+        }
+        let decompile_cache_mb = matches
+            .value_of("decompile_cache_mb")
+            .ok_or_else(|| ConfigError::MissingRequiredField("decompile_cache_mb".to_string()))?
+            .parse::<u64>()
+            .map_err(|e| ConfigError::ParseError(format!("decompile_cache_mb: {}", e)))?;
+        if let Some(dir) = path_arg("cache_dir", workspace::env::CACHE_DIR) {
+            workspace.cache_dir = dir;
+        }
+        let jobs = matches
+            .value_of("jobs")
+            .ok_or_else(|| ConfigError::MissingRequiredField("jobs".to_string()))?
+            .parse::<usize>()
+            .map_err(|e| ConfigError::ParseError(format!("jobs: {}", e)))?;
+        if jobs == 0 {
+            return Err(ConfigError::ParseError("jobs must be at least 1".to_string()));
+        }
+        let max_depth = matches
+            .value_of("max_depth")
+            .ok_or_else(|| ConfigError::MissingRequiredField("max_depth".to_string()))?
+            .parse::<i32>()
+            .map_err(|e| ConfigError::ParseError(format!("max_depth: {}", e)))?;
+        let max_nodes = matches
+            .value_of("max_nodes")
+            .ok_or_else(|| ConfigError::MissingRequiredField("max_nodes".to_string()))?
+            .parse::<usize>()
+            .map_err(|e| ConfigError::ParseError(format!("max_nodes: {}", e)))?;
+        let graph_format = matches
+            .value_of("graph_format")
+            .map(|format| format.parse::<GraphFormat>().map_err(ConfigError::ParseError))
+            .transpose()?;
+
         Ok(Config {
             platform,
             logic_address,
             storage_address,
             block_number,
+            chains,
+            offline,
+            bytecode,
+            createbin: matches.is_present("createbin"),
+            combined: matches.is_present("combined"),
+            storage_snapshot,
+            workspace,
+            keep_temp: matches.is_present("keep_temp"),
+            gigahorse,
+            decompile_cache: Arc::new(DecompileCache::new(decompile_cache_mb * 1024 * 1024)),
+            deployments: Arc::new(deployments),
+            jobs,
+            graph_format,
+            max_depth,
+            max_nodes,
+            tx,
+            tx_hash,
+        })
+    }
+
+    fn parse_tx_hash(hash: &str) -> Result<H256, ConfigError> {
+        let bytes = decode_hex(hash)
+            .map_err(|e| ConfigError::ParseError(format!("tx hash {}: {}", hash, e)))?;
+        if bytes.len() != 32 {
+            return Err(ConfigError::ParseError(format!("tx hash {}: expected 32 bytes", hash)));
+        }
+        Ok(H256::from_slice(&bytes))
+    }
+
+    fn parse_tx_address(name: &str, addr: &str) -> Result<String, ConfigError> {
+        if !is_valid_ethereum_address(addr) {
+            return Err(ConfigError::ParseError(format!("{}: invalid address {}", name, addr)));
+        }
+        Ok(addr.to_lowercase())
+    }
+
+    /// Wei amount in decimal or 0x-prefixed hex
+    fn parse_value(value: &str) -> Result<U256, ConfigError> {
+        let parsed = match value.strip_prefix("0x") {
+            Some(hex) => U256::from_str_radix(hex, 16).map_err(|e| e.to_string()),
+            None => U256::from_dec_str(value).map_err(|e| e.to_string()),
+        };
+        parsed.map_err(|e| ConfigError::ParseError(format!("value: {}", e)))
+    }
+
+    fn read_bytecode(path: &Path) -> Result<Vec<u8>, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            ConfigError::OfflineInput(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        decode_hex(&content).map_err(|e| {
+            ConfigError::OfflineInput(format!("invalid bytecode in {}: {}", path.display(), e))
         })
     }
 
     /// Validate the configuration
     pub fn validate(&self) -> Result<(), ConfigError> {
-        if self.logic_address.is_empty() {
+        // the target of a transaction is validated once it is fetched
+        let awaiting_tx = self.tx_hash.is_some() && self.logic_address.is_empty();
+
+        if self.logic_address.is_empty() && !awaiting_tx {
             return Err(ConfigError::MissingRequiredField("logic_address".to_string()));
         }
         
-        if !self.logic_address.starts_with("0x") || self.logic_address.len() != 42 {
+        if !awaiting_tx && (!self.logic_address.starts_with("0x") || self.logic_address.len() != 42) {
             return Err(ConfigError::ParseError("Invalid Ethereum address format".to_string()));
         }
 
+        if !self.offline && !self.chain().has_endpoint() {
+            return Err(ConfigError::UnknownPlatform(self.platform.clone()));
+        }
+
         Ok(())
     }
+
+    /// Connection settings of the configured platform
+    pub fn chain(&self) -> ChainConfig {
+        self.chains.get(&self.platform).cloned().unwrap_or_default()
+    }
+}
+
+/// Environment of the frame making the analyzed call, used to resolve
+/// `msg.sender`, `tx.origin` and `address(this)` passed as arguments; empty
+/// fields are unknown
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CallerEnv {
+    /// msg.sender of the calling frame
+    pub sender: String,
+    /// Address the calling frame executes as (its storage context)
+    pub address: String,
+    pub origin: String,
 }
 
 /// Source structure for analysis context
@@ -145,29 +582,121 @@ pub struct AnalysisSource {
     pub func_sign: String,
     pub block_number: u64,
     pub caller: String,
+    pub caller_code_key: String,
     pub caller_func_sign: String,
     pub call_site: String,
     pub level: i32,
+    pub provider: Arc<dyn StateProvider>,
+    pub workspace: Workspace,
+    pub gigahorse: GigahorseRunner,
+    pub decompile_cache: Arc<DecompileCache>,
+    pub deployments: Arc<DeploymentIndex>,
+    pub caller_env: CallerEnv,
+    /// Analyze the creation code, even if runtime code is deployed
+    pub createbin: bool,
+    /// Addresses the constructor stored by slot, for slots empty on chain
+    pub constructor_storage: Arc<HashMap<String, String>>,
+    /// Wei sent with the call, when known
+    pub value: Option<U256>,
 }
 
 impl AnalysisSource {
     /// Create a new analysis source from configuration
-    pub fn from_config(config: &Config, func_sign: String) -> Self {
+    pub fn from_config(config: &Config, provider: Arc<dyn StateProvider>, func_sign: String) -> Self {
         Self {
             platform: config.platform.clone(),
             logic_addr: config.logic_address.clone(),
             storage_addr: config.storage_address.clone(),
             func_sign,
             block_number: config.block_number,
-            caller: defaults::CALLER.to_string(),
+            caller: config
+                .tx
+                .sender
+                .clone()
+                .unwrap_or_else(|| defaults::CALLER.to_string()),
+            caller_code_key: String::new(),
             caller_func_sign: String::new(),
             call_site: String::new(),
             level: defaults::LEVEL,
+            provider,
+            workspace: config.workspace.clone(),
+            gigahorse: config.gigahorse.clone(),
+            decompile_cache: config.decompile_cache.clone(),
+            deployments: config.deployments.clone(),
+            // the entry function is called by the transaction itself
+            caller_env: CallerEnv {
+                sender: String::new(),
+                address: config.tx.sender.clone().unwrap_or_default(),
+                origin: config.tx.origin.clone().unwrap_or_default(),
+            },
+            value: config.tx.value,
+            createbin: false,
+            constructor_storage: Arc::default(),
         }
     }
 
     /// Create a source for createbin analysis
-    pub fn for_createbin(config: &Config) -> Self {
-        Self::from_config(config, defaults::CREATEBIN_FUNC_SELECTOR.to_string())
+    pub fn for_createbin(config: &Config, provider: Arc<dyn StateProvider>) -> Self {
+        Self {
+            createbin: true,
+            ..Self::from_config(config, provider, defaults::CREATEBIN_FUNC_SELECTOR.to_string())
+        }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+impl AnalysisSource {
+    /// Source of an entry function of `logic_addr`, reading state from an
+    /// empty mock and a workspace without toolchain
+    pub(crate) fn for_test(logic_addr: &str) -> Self {
+        Self {
+            platform: defaults::BLOCKCHAIN_PLATFORM.to_string(),
+            logic_addr: logic_addr.to_string(),
+            storage_addr: logic_addr.to_string(),
+            func_sign: "0x12345678".to_string(),
+            block_number: defaults::BLOCK_NUMBER,
+            caller: defaults::CALLER.to_string(),
+            caller_code_key: String::new(),
+            caller_func_sign: String::new(),
+            call_site: String::new(),
+            level: defaults::LEVEL,
+            provider: Arc::new(crate::provider::MockProvider::new()),
+            workspace: Workspace::new(PathBuf::from("./no-such-toolchain")),
+            gigahorse: GigahorseRunner::new(PathBuf::new(), Duration::from_secs(1)),
+            decompile_cache: Arc::new(DecompileCache::default()),
+            deployments: Arc::new(DeploymentIndex::new()),
+            caller_env: CallerEnv::default(),
+            value: Some(U256::zero()),
+            createbin: false,
+            constructor_storage: Arc::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tx_context() {
+        assert_eq!(Config::parse_value("1000").unwrap(), U256::from(1000));
+        assert_eq!(
+            Config::parse_value("0xde0b6b3a7640000").unwrap(),
+            U256::exp10(18)
+        );
+        assert!(Config::parse_value("1 ether").is_err());
+        assert_eq!(
+            Config::parse_tx_address("origin", "0x10C509AA9ab291C76c45414e7CdBd375e1D5AcE8")
+                .unwrap(),
+            "0x10c509aa9ab291c76c45414e7cdbd375e1d5ace8"
+        );
+        assert!(Config::parse_tx_address("sender", "msg.sender").is_err());
+    }
+
+    #[test]
+    fn test_missing_endpoint_error() {
+        let message = ConfigError::UnknownPlatform("eth".to_string()).to_string();
+        assert!(message.contains("--chain_config"));
+        assert!(message.contains("LYDIA_ETH_RPC_URL"));
+    }
+}
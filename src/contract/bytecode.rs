@@ -0,0 +1,166 @@
+//! Helpers working directly on raw EVM bytecode, before any decompilation.
+
+use web3::signing::keccak256;
+use web3::types::{Address, H256};
+
+const CODECOPY: u8 = 0x39;
+const PUSH1: u8 = 0x60;
+const PUSH32: u8 = 0x7f;
+const RETURN: u8 = 0xf3;
+const INVALID: u8 = 0xfe;
+
+/// Free memory pointer setup emitted by solc at the start of every contract
+const SOLC_PREAMBLES: [[u8; 5]; 2] = [
+    [0x60, 0x80, 0x60, 0x40, 0x52],
+    [0x60, 0x60, 0x60, 0x40, 0x52],
+];
+
+/// Decode a hex string (with or without `0x` and surrounding whitespace)
+pub fn decode_hex(code: &str) -> Result<Vec<u8>, hex::FromHexError> {
+    let code = code.trim();
+    hex::decode(code.strip_prefix("0x").unwrap_or(code))
+}
+
+/// Heuristically decide whether `code` is creation (init) bytecode: a
+/// constructor that `CODECOPY`s an embedded runtime, `RETURN`s it and is
+/// followed by `INVALID` and a runtime starting with the solc preamble.
+pub fn is_creation_bytecode(code: &[u8]) -> bool {
+    let mut seen_codecopy = false;
+    let mut pc = 0;
+    while pc < code.len() {
+        let op = code[pc];
+        if op == CODECOPY {
+            seen_codecopy = true;
+        }
+        if op == RETURN && seen_codecopy && code.get(pc + 1) == Some(&INVALID) {
+            let runtime = &code[pc + 2..];
+            if SOLC_PREAMBLES
+                .iter()
+                .any(|preamble| runtime.starts_with(preamble))
+            {
+                return true;
+            }
+        }
+        pc += match op {
+            PUSH1..=PUSH32 => (op - PUSH1) as usize + 2,
+            _ => 1,
+        };
+    }
+    false
+}
+
+/// Stable placeholder address for bytecode analyzed without a deployment
+pub fn synthetic_address(code: &[u8]) -> Address {
+    Address::from_slice(&keccak256(code)[12..])
+}
+
+/// Address of the contract `deployer` creates with CREATE at `nonce`:
+/// `keccak256(rlp([deployer, nonce]))[12..]`
+pub fn create_address(deployer: &Address, nonce: u64) -> Address {
+    let nonce_bytes = nonce.to_be_bytes();
+    let nonce_bytes = &nonce_bytes[nonce.leading_zeros() as usize / 8..];
+    // 20-byte string and nonce as a short RLP list
+    let mut rlp = vec![0, 0x80 + 20];
+    rlp.extend_from_slice(deployer.as_bytes());
+    match nonce_bytes {
+        [] => rlp.push(0x80),
+        [byte] if *byte < 0x80 => rlp.push(*byte),
+        bytes => {
+            rlp.push(0x80 + bytes.len() as u8);
+            rlp.extend_from_slice(bytes);
+        }
+    }
+    rlp[0] = 0xc0 + (rlp.len() - 1) as u8;
+    Address::from_slice(&keccak256(&rlp)[12..])
+}
+
+/// Address of the contract `deployer` creates with CREATE2 (EIP-1014):
+/// `keccak256(0xff ++ deployer ++ salt ++ keccak256(init_code))[12..]`
+pub fn create2_address(deployer: &Address, salt: &H256, init_code: &[u8]) -> Address {
+    let mut preimage = Vec::with_capacity(85);
+    preimage.push(0xff);
+    preimage.extend_from_slice(deployer.as_bytes());
+    preimage.extend_from_slice(salt.as_bytes());
+    preimage.extend_from_slice(&keccak256(init_code));
+    Address::from_slice(&keccak256(&preimage)[12..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // constructor copying and returning its embedded runtime
+    const CREATION: &str =
+        "6080604052348015600f57600080fd5b50600680601d6000396000f3fe6080604052600080fd";
+    const RUNTIME: &str = "6080604052600080fdfea2646970667358";
+
+    #[test]
+    fn test_is_creation_bytecode() {
+        assert!(is_creation_bytecode(&decode_hex(CREATION).unwrap()));
+        assert!(!is_creation_bytecode(&decode_hex(RUNTIME).unwrap()));
+    }
+
+    #[test]
+    fn test_push_data_is_not_decoded_as_opcodes() {
+        // PUSH4 0x39f3fe60 hides CODECOPY/RETURN/INVALID bytes in its immediate
+        let code = decode_hex("0x6339f3fe60608060405200").unwrap();
+        assert!(!is_creation_bytecode(&code));
+    }
+
+    #[test]
+    fn test_synthetic_address_is_stable() {
+        let code = decode_hex(RUNTIME).unwrap();
+        assert_eq!(synthetic_address(&code), synthetic_address(&code));
+        assert_ne!(synthetic_address(&code), Address::zero());
+    }
+
+    #[test]
+    fn test_create_address() {
+        let deployer: Address = "0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0"
+            .parse()
+            .unwrap();
+        let expected: [(u64, &str); 3] = [
+            (0, "0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d"),
+            (1, "0x343c43a37d37dff08ae8c4a11544c718abb4fcf8"),
+            (2, "0xf778b86fa74e846c4f0a1fbd1335fe81c00a0c91"),
+        ];
+        for (nonce, addr) in expected {
+            assert_eq!(create_address(&deployer, nonce), addr.parse().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_create2_address() {
+        // examples of EIP-1014
+        let deployer: Address = "0xdeadbeef00000000000000000000000000000000"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            create2_address(&Address::zero(), &H256::zero(), &[0x00]),
+            "0x4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38"
+                .parse()
+                .unwrap()
+        );
+        assert_eq!(
+            create2_address(&deployer, &H256::zero(), &[0x00]),
+            "0xb928f69bb1d91cd65274e3c79d8986362984fda3"
+                .parse()
+                .unwrap()
+        );
+        let salt: H256 = "0x00000000000000000000000000000000000000000000000000000000cafebabe"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            create2_address(
+                &"0x00000000000000000000000000000000deadbeef"
+                    .parse()
+                    .unwrap(),
+                &salt,
+                &decode_hex("deadbeef").unwrap()
+            ),
+            "0x60f3f640a8508fc6a86d45df051962668e1e8ac7"
+                .parse()
+                .unwrap()
+        );
+    }
+}
@@ -1,32 +1,89 @@
 use csv::{ReaderBuilder, StringRecord};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::io::{self};
+use std::fmt;
+use std::fs;
 use std::mem;
-use std::process::Command;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Instant;
-use std::{fs, path::Path};
-use tokio::fs::File;
-use tokio::io::AsyncWriteExt;
-use web3::types::Address;
+use web3::types::{Address, BlockNumber, H256, U256};
 
+use crate::config::{AnalysisSource, CallerEnv};
+use crate::contract::bytecode::{create2_address, create_address, decode_hex};
 use crate::contract::data_structure;
-use crate::contract::status_fetcher::Web3Transport;
+use crate::contract::decompile_cache::DecompileCache;
+use crate::contract::deployment::DeploymentIndex;
+use crate::contract::diamond;
+use crate::contract::gigahorse::GigahorseRunner;
+use crate::contract::proxy::{MinimalProxy, ProxyLayout};
+use crate::contract::status_fetcher::{array_element_slot, mapping_slot, storage_word_to_addr};
+use crate::error::LydiaError;
+use crate::outputter::result_structure::{
+    CreatedContract, ResolvedProxy, UnresolvedCall, UnresolvedCallee,
+};
+use crate::provider::StateProvider;
+use crate::utils::is_valid_ethereum_address;
+use crate::workspace::Workspace;
 
+use super::data_structure::CalleeResolution;
 use super::data_structure::ExternalCall;
 use super::data_structure::ExternalCallData;
 
-const CONTRACT_PATH: &str = "./gigahorse-toolchain/contracts/";
-const CONTRACT_DIR: &str = "./contracts/";
-const TEMP_PATH: &str = "./gigahorse-toolchain/.temp/";
-const ANALYSIS: &str = "Leslie";
+/// Reason of a call no Leslie relation gives a callee for
+const NO_CALLEE_SOURCE: &str = "no constant, storage or argument source for the callee";
+/// Selector Gigahorse gives the fallback function
+const FALLBACK_FUNC_SIGN: &str = "0x00000000";
 
-#[derive(Debug)]
-enum ValueType {
+/// Value the caller passed for an argument at the call site
+#[derive(Debug, Clone, PartialEq)]
+enum ArgValue {
     Int(i32),
     Float(f64),
     Str(String),
+    /// An environment value of the caller's frame
+    Env(EnvValue),
+}
+
+/// Environment opcodes whose result the caller may pass on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EnvValue {
+    Caller,
+    Origin,
+    This,
+}
+
+impl EnvValue {
+    fn from_opcode(opcode: &str) -> Option<Self> {
+        match opcode {
+            "CALLER" => Some(EnvValue::Caller),
+            "ORIGIN" => Some(EnvValue::Origin),
+            "ADDRESS" => Some(EnvValue::This),
+            _ => None,
+        }
+    }
+
+    /// The address the value stands for in the frame of `env`, when known
+    fn resolve(&self, env: &CallerEnv) -> Option<String> {
+        let value = match self {
+            EnvValue::Caller => &env.sender,
+            EnvValue::Origin => &env.origin,
+            EnvValue::This => &env.address,
+        };
+        is_valid_ethereum_address(value).then(|| value.to_lowercase())
+    }
+}
+
+impl fmt::Display for EnvValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvValue::Caller => write!(f, "msg.sender"),
+            EnvValue::Origin => write!(f, "tx.origin"),
+            EnvValue::This => write!(f, "address(this)"),
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -37,65 +94,100 @@ pub struct Contract {
     pub(crate) func_sign: String,
     block_number: u64,
     pub(crate) caller: String,
+    caller_code_key: String,
     pub(crate) call_site: String,
     pub(crate) level: i32,
+    code_key: String,
     origin: bool,
     func: String,
     func_sign_dict: HashMap<String, String>,
+    /// High-level names of public functions whose signature is known
+    func_names: HashMap<String, String>,
     pub(crate) func_sign_list: Vec<String>,
     pub(crate) external_call_in_func_signature: HashSet<String>,
-    call_arg_vals: HashMap<i32, ValueType>,
-    url: String,
+    call_arg_vals: HashMap<i32, ArgValue>,
+    caller_env: CallerEnv,
+    provider: Arc<dyn StateProvider>,
+    workspace: Workspace,
+    gigahorse: GigahorseRunner,
+    decompile_cache: Arc<DecompileCache>,
+    deployments: Arc<DeploymentIndex>,
     pub external_calls: Vec<ExternalCall>,
+    pub(crate) unresolved_callees: Vec<UnresolvedCallee>,
+    /// Contracts deployed by the analyzed functions
+    pub(crate) created_contracts: Vec<CreatedContract>,
+    /// Standard proxy layout of the storage contract, once found
+    pub(crate) proxy: Option<ResolvedProxy>,
+    /// Whether the storage contract was probed for every standard layout
+    proxy_probed: bool,
+    /// Kind, implementation and DELEGATECALL offset of a minimal proxy
+    minimal_proxy: Option<(MinimalProxy, Address, usize)>,
+    /// Whether the storage contract is a diamond loupe, once checked
+    diamond_loupe: Option<bool>,
+    /// Facets of the storage contract and their selectors, once asked
+    diamond_facets: Option<Vec<(Address, Vec<[u8; 4]>)>>,
     createbin: bool,
+    /// Analyze the creation code even if runtime code is deployed
+    analyze_creation: bool,
+    constructor_storage: Arc<HashMap<String, String>>,
     storage_space: HashMap<String, String>,
     // data reader
     constant_callee_df: HashMap<String, data_structure::ConstantCallee>,
     storage_callee_df: HashMap<String, data_structure::StorageCallee>,
     storage_callee_proxy_df: HashMap<String, data_structure::ProxyStorageCallee>,
     func_arg_callee_df: HashMap<String, data_structure::FuncArgCallee>,
+    storage_element_callee_df: HashMap<String, data_structure::StorageElementCallee>,
     constant_func_sign_df: HashMap<String, data_structure::ConstantFuncSign>,
     proxy_func_sign_df: HashMap<String, data_structure::ProxyFuncSign>,
 }
 
 #[allow(dead_code)]
 impl Contract {
-    pub fn new(
-        platform: String,
-        logic_addr: String,
-        storage_addr: String,
-        func_sign: String,
-        block_number: u64,
-        caller: String,
-        call_site: String,
-        level: i32,
-    ) -> Contract {
+    pub fn from_source(source: &AnalysisSource) -> Contract {
         // Initialize a Contract instance
-        let formatted_logic_addr = Self::format_addr(&logic_addr);
-        let formatted_storage_addr = Self::format_addr(&storage_addr);
+        let formatted_logic_addr = Self::format_addr(&source.logic_addr);
+        let formatted_storage_addr = Self::format_addr(&source.storage_addr);
         Contract {
-            platform,
-            logic_addr: formatted_logic_addr,
+            platform: source.platform.clone(),
+            logic_addr: formatted_logic_addr.clone(),
             storage_addr: formatted_storage_addr,
-            func_sign: func_sign.clone(),
-            origin: func_sign.is_empty(),
+            func_sign: source.func_sign.clone(),
+            origin: source.func_sign.is_empty(),
             func: String::new(),
             func_sign_dict: HashMap::new(),
+            func_names: HashMap::new(),
             func_sign_list: Vec::new(),
             external_call_in_func_signature: HashSet::new(),
             call_arg_vals: HashMap::new(),
-            url: String::new(),
+            caller_env: source.caller_env.clone(),
+            provider: source.provider.clone(),
+            workspace: source.workspace.clone(),
+            gigahorse: source.gigahorse.clone(),
+            decompile_cache: source.decompile_cache.clone(),
+            deployments: source.deployments.clone(),
             external_calls: Vec::new(),
+            unresolved_callees: Vec::new(),
+            created_contracts: Vec::new(),
+            proxy: None,
+            proxy_probed: false,
+            minimal_proxy: None,
+            diamond_loupe: None,
+            diamond_facets: None,
             storage_space: HashMap::new(),
-            block_number,
-            caller,
-            call_site,
-            level,
+            block_number: source.block_number,
+            caller: source.caller.clone(),
+            caller_code_key: source.caller_code_key.clone(),
+            call_site: source.call_site.clone(),
+            level: source.level,
+            code_key: String::new(),
             createbin: false,
+            analyze_creation: source.createbin,
+            constructor_storage: source.constructor_storage.clone(),
             constant_callee_df: HashMap::new(),
             storage_callee_df: HashMap::new(),
             storage_callee_proxy_df: HashMap::new(),
             func_arg_callee_df: HashMap::new(),
+            storage_element_callee_df: HashMap::new(),
             constant_func_sign_df: HashMap::new(),
             proxy_func_sign_df: HashMap::new(),
         }
@@ -105,8 +197,10 @@ impl Contract {
         let start = Instant::now();
         self.download_bytecode().await?;
         // if code exists, go on analyzing
-        let path = format!("{}{}.hex", CONTRACT_PATH, self.logic_addr);
-        if Path::new(&path).exists() {
+        if let Some((kind, implementation, delegatecall)) = self.minimal_proxy {
+            self.delegate_to_implementation(kind, implementation, delegatecall)
+                .await;
+        } else if !self.code_key.is_empty() {
             // Perform analysis
             self.analyze_contract().await?;
         }
@@ -142,92 +236,245 @@ impl Contract {
         &self.external_call_in_func_signature
     }
 
-    fn set_url(&mut self) {
-        self.url = match self.platform.as_str() {
-            "ETH" => "https://go.getblock.io/f3866d56275945e2a8a0d6c5537331f4".to_string(),
-            "BSC" => "https://go.getblock.io/3937fb5368654fe38d1736304fa584c3".to_string(),
-            _ => "".to_string(),
-        };
+    /// Key of the stored bytecode and its Gigahorse output: the keccak hash
+    /// of the analyzed (runtime or creation) code, empty when there is none
+    pub fn code_key(&self) -> &str {
+        &self.code_key
+    }
+
+    fn bytecode_path(&self) -> PathBuf {
+        self.workspace.bytecode_path(&self.code_key)
+    }
+
+    fn relation_path(&self, relation: &str) -> String {
+        self.relation_path_of(&self.code_key, relation)
+    }
+
+    fn relation_path_of(&self, code_key: &str, relation: &str) -> String {
+        self.workspace
+            .relation_path(code_key, relation)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Snapshot block every code and storage read is pinned to
+    fn block(&self) -> BlockNumber {
+        BlockNumber::Number(self.block_number.into())
     }
 
     async fn download_bytecode(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        self.set_url();
-        if self.url.is_empty() {
-            error!("URL is empty");
+        // contracts obtained from replay are marked by an empty "0x" code file
+        let replay_marker = self.workspace.bytecode_path(&self.logic_addr);
+        let replayed_empty = fs::read_to_string(replay_marker)
+            .map(|bin| bin == "0x")
+            .unwrap_or(false);
+        let contract_address: Address = self.logic_addr.parse().expect("Invalid contract address");
+        let code = if replayed_empty || self.analyze_creation {
+            Vec::new()
+        } else {
+            // get bytecode at the snapshot block from the state provider
+            match self
+                .provider
+                .get_code(contract_address, Some(self.block()))
+                .await
+            {
+                Ok(code) => code,
+                Err(LydiaError::StateUnavailable(reason)) => {
+                    self.unresolved_callees.push(UnresolvedCallee {
+                        caller: self.caller.clone(),
+                        call_site: self.call_site.clone(),
+                        target: self.logic_addr.clone(),
+                        reason,
+                    });
+                    return Ok(());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+        // Check if the code is not the zero-byte code.
+        if !code.is_empty() {
+            self.minimal_proxy = MinimalProxy::detect(&code);
+            self.code_key = self.workspace.write_bytecode(&code)?;
             return Ok(());
         }
-        let loc: String = format!("{}{}.hex", CONTRACT_PATH, self.logic_addr);
-        if Path::new(&loc).exists() {
-            // Read the file
-            // ... handle file reading and conditional logic
-            let bin = fs::read_to_string(&loc)?;
-            if bin == "0x" {
-                // use contracts obtained from replay, but indeed, the creation code could be obtrained from the first deployment transaction
-                let bin_content_path = format!(
-                    "{}createbin/{}_createbin.hex",
-                    CONTRACT_PATH, self.logic_addr
+        // no runtime code at this block (not deployed yet, self-destructed or
+        // constructor-only), fall back to the creation code
+        if let Some(creation_code) = self.creation_code(&contract_address).await? {
+            self.code_key = self.workspace.write_bytecode(&creation_code)?;
+            self.createbin = true;
+            // Assume createbin only has constructor
+            self.func_sign = "__function_selector__".to_string();
+            self.func_sign_list = vec!["__function_selector__".to_string()];
+        }
+
+        Ok(())
+    }
+
+    /// Creation code taken from the state provider, the createbin folder or
+    /// the deployment transaction, which is kept in the createbin folder
+    async fn creation_code(
+        &self,
+        contract_address: &Address,
+    ) -> Result<Option<Vec<u8>>, LydiaError> {
+        if let Some(code) = self.provider.get_creation_code(*contract_address).await? {
+            return Ok(Some(code));
+        }
+        let bin_content_path = self.workspace.createbin_path(&self.logic_addr);
+        if let Some(code) = fs::read_to_string(bin_content_path)
+            .ok()
+            .and_then(|bin_content| decode_hex(&bin_content).ok())
+        {
+            return Ok(Some(code));
+        }
+        let code = self
+            .deployments
+            .creation_code(self.provider.as_ref(), contract_address)
+            .await?;
+        if let Some(code) = &code {
+            if let Err(e) = self.workspace.write_createbin(&self.logic_addr, code) {
+                warn!(
+                    "Failed to keep the creation code of {}: {}",
+                    self.logic_addr, e
                 );
-                if let Ok(bin_content) = fs::read_to_string(bin_content_path) {
-                    fs::write(&loc, &bin_content[2..])?;
-                    self.createbin = true;
-                    // Assume createbin only has constructor
-                    self.func_sign = "__function_selector__".to_string();
-                    self.func_sign_list = vec!["__function_selector__".to_string()];
+            }
+        }
+        Ok(code)
+    }
+
+    /// A minimal proxy has no function of its own: every call is delegated
+    /// to its implementation on the storage of the clone, without decompiling
+    /// the clone
+    async fn delegate_to_implementation(
+        &mut self,
+        kind: MinimalProxy,
+        implementation: Address,
+        delegatecall: usize,
+    ) {
+        info!(
+            "{} is a {:?} clone of {:?}",
+            self.logic_addr, kind, implementation
+        );
+        // an entry analysis covers every public function of the
+        // implementation, or the fallback receiving all calls
+        let func_signs = if self.origin {
+            let mut selectors = match self.implementation_selectors(implementation).await {
+                Ok(selectors) => selectors,
+                Err(e) => {
+                    warn!(
+                        "Failed to recover the functions of {:?}: {}",
+                        implementation, e
+                    );
+                    Vec::new()
                 }
+            };
+            if selectors.is_empty() {
+                selectors.push(FALLBACK_FUNC_SIGN.to_string());
             }
+            self.func_sign_list = selectors.clone();
+            self.external_call_in_func_signature
+                .extend(selectors.iter().cloned());
+            selectors
         } else {
-            // Perform network request to get bytecode
-            // rpc methods are put into the status_fetcher module
-            let contract_address: Address =
-                self.logic_addr.parse().expect("Invalid contract address");
-            let transport = Web3Transport::new(&self.url).await?;
-            let code = transport.get_code(contract_address).await?;
-            let hex_string = hex::encode(code.0.clone());
-            // Check if the code is not the zero-byte code.
-            if !code.0.is_empty() {
-                // Use tokio's async file writing methods to write the code to a file.
-                let mut file = File::create(&loc).await?;
-                file.write_all(hex_string.as_bytes()).await?; // Skip the leading '0x' if present and finish code fetching
-            }
+            vec![self.func_sign.clone()]
+        };
+        for func_sign in func_signs {
+            self.external_calls.push(ExternalCall {
+                target_logic_addr: format!("{:?}", implementation),
+                target_storage_addr: self.storage_addr.clone(),
+                target_func_sign: func_sign.clone(),
+                call_site: self.call_site.clone(),
+                caller_func_sign: func_sign,
+                caller_addr: self.caller.clone(),
+                caller_code_key: self.caller_code_key.clone(),
+                call_stmt: format!("{:#x}", delegatecall),
+                call_op: "DELEGATECALL".to_string(),
+                resolution: CalleeResolution::MinimalProxy { kind },
+            });
         }
-
-        Ok(())
     }
 
-    async fn analyze_contract(&mut self) -> io::Result<()> {
-        // Call external command to analyze the contract
-        let command = format!(
-            "cd ./gigahorse-toolchain && ./gigahorse.py -C ./clients/leslie.dl {}{}.hex >/dev/null 2>&1",
-            CONTRACT_DIR, self.logic_addr
-        );
-        // Execute the command
-        let status = Command::new("sh").arg("-c").arg(command).status()?;
+    /// Public function selectors of the implementation of a clone, from its
+    /// decompiled code
+    async fn implementation_selectors(
+        &self,
+        implementation: Address,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let code = self
+            .provider
+            .get_code(implementation, Some(self.block()))
+            .await?;
+        if code.is_empty() {
+            return Ok(Vec::new());
+        }
+        let code_key = self.workspace.write_bytecode(&code)?;
+        self.decompile(&code_key).await?;
+        let mut selectors: Vec<String> = Self::read_rows::<(String, String)>(
+            &self.relation_path_of(&code_key, "FunctionSelector"),
+        )?
+        .into_iter()
+        .map(|(_func, func_sign)| func_sign)
+        .filter(|func_sign| func_sign != "__function_selector__")
+        .collect();
+        selectors.sort();
+        selectors.dedup();
+        Ok(selectors)
+    }
 
-        if !status.success() {
-            error!("Command executed with failing error code");
+    /// Decompile `code_key` and run the Leslie client on it, unless the same
+    /// code was decompiled before
+    async fn decompile(&self, code_key: &str) -> Result<(), LydiaError> {
+        let _claim = self.decompile_cache.claim(code_key).await;
+        if !self.decompile_cache.lookup(&self.workspace, code_key) {
+            self.gigahorse
+                .run(&self.workspace, &self.workspace.bytecode_path(code_key))
+                .await?;
+            if let Err(e) = self.decompile_cache.store(&self.workspace, code_key) {
+                warn!("Failed to cache decompiled {}: {}", code_key, e);
+            }
         }
+        Ok(())
+    }
+
+    async fn analyze_contract(&mut self) -> Result<(), LydiaError> {
+        self.decompile(&self.code_key.clone()).await?;
         // binding functions
         let _ = self.set_func();
+        let _ = self.set_func_names();
         // extract known call arguments, constants etc.
         let _ = self.set_call_arg_vals();
         // prepare for the call info
-        let _ = self.set_call_info();
+        self.set_call_info();
 
         let start = Instant::now();
         if self.origin {
             for func in self.func_sign_dict.clone().keys() {
-                let _ = self
-                    .set_external_calls(func, &self.func_sign_dict[func].clone())
-                    .await;
+                let func_sign = self.func_sign_dict[func].clone();
+                if let Err(e) = self.set_external_calls(func, &func_sign).await {
+                    warn!(
+                        "Failed to recover the external calls of {} in {}: {}",
+                        func_sign, self.logic_addr, e
+                    );
+                }
             }
         } else {
             let func_clone = self.func.clone();
             let func_sign_clone = self.func_sign.clone();
-            let _ = self.set_external_calls(&func_clone, &func_sign_clone).await;
+            if let Err(e) = self.set_external_calls(&func_clone, &func_sign_clone).await {
+                warn!(
+                    "Failed to recover the external calls of {} in {}: {}",
+                    func_sign_clone, self.logic_addr, e
+                );
+            }
         }
         let duration = start.elapsed();
         info!("contract external calls: {:?}", self.external_calls);
         info!("external call recovery consumption time: {:?}", duration);
+        if let Err(e) = self.set_created_contracts() {
+            warn!(
+                "Failed to recover the contracts {} creates: {}",
+                self.logic_addr, e
+            );
+        }
         Ok(())
     }
 
@@ -247,36 +494,31 @@ impl Contract {
 
         // type1: constant callee written in the contract
         self.read_csv::<data_structure::ConstantCallee>(
-            &format!(
-                "{}{}/out/{}_ExternalCall_Callee_ConstType.csv",
-                TEMP_PATH, self.logic_addr, ANALYSIS
-            ),
+            &self.relation_path("ExternalCall_Callee_ConstType"),
             &mut constant_callee_df,
         )?;
         // type2: callee stored in the storage-type state variable
         self.read_csv::<data_structure::StorageCallee>(
-            &format!(
-                "{}{}/out/{}_ExternalCall_Callee_StorageType.csv",
-                TEMP_PATH, self.logic_addr, ANALYSIS
-            ),
+            &self.relation_path("ExternalCall_Callee_StorageType"),
             &mut storage_callee_df,
         )?;
         // type3: proxy callee stored in the storage-type state variable
         self.read_csv::<data_structure::ProxyStorageCallee>(
-            &format!(
-                "{}{}/out/{}_ExternalCall_Callee_StorageType_ForProxy.csv",
-                TEMP_PATH, self.logic_addr, ANALYSIS
-            ),
+            &self.relation_path("ExternalCall_Callee_StorageType_ForProxy"),
             &mut storage_callee_proxy_df,
         )?;
         // type4: callee flowed from function arguments
         self.read_csv::<data_structure::FuncArgCallee>(
-            &format!(
-                "{}{}/out/{}_ExternalCall_Callee_FuncArgType.csv",
-                TEMP_PATH, self.logic_addr, ANALYSIS
-            ),
+            &self.relation_path("ExternalCall_Callee_FuncArgType"),
             &mut func_arg_callee_df,
         )?;
+        // type5: callee stored in an element of a mapping or array
+        self.storage_element_callee_df = Self::read_rows::<data_structure::StorageElementCallee>(
+            &self.relation_path("ExternalCall_Callee_StorageElementType"),
+        )?
+        .into_iter()
+        .map(|data| (data.call_stmt.clone(), data))
+        .collect();
         // Put the vectors back
         self.constant_callee_df = constant_callee_df;
         self.storage_callee_df = storage_callee_df;
@@ -292,6 +534,10 @@ impl Contract {
             "function argument callee info: {:?}",
             self.func_arg_callee_df
         );
+        info!(
+            "storage element callee info: {:?}",
+            self.storage_element_callee_df
+        );
         Ok(())
     }
 
@@ -301,18 +547,12 @@ impl Contract {
         let mut proxy_func_sign_df = mem::take(&mut self.proxy_func_sign_df);
         // type1: constant func sign written in the contract
         self.read_csv::<data_structure::ConstantFuncSign>(
-            &format!(
-                "{}{}/out/{}_ExternalCall_FuncSign_ConstType.csv",
-                TEMP_PATH, self.logic_addr, ANALYSIS
-            ),
+            &self.relation_path("ExternalCall_FuncSign_ConstType"),
             &mut constant_func_sign_df,
         )?;
         // type2: proxy func sign
         self.read_csv::<data_structure::ProxyFuncSign>(
-            &format!(
-                "{}{}/out/{}_ExternalCall_FuncSign_ProxyType.csv",
-                TEMP_PATH, self.logic_addr, ANALYSIS
-            ),
+            &self.relation_path("ExternalCall_FuncSign_ProxyType"),
             &mut proxy_func_sign_df,
         )?;
         // Put the vectors back
@@ -332,9 +572,7 @@ impl Contract {
         data: &mut HashMap<String, T>,
     ) -> Result<(), Box<dyn Error>> {
         if Path::new(&file_path).exists() {
-            let mut rdr = ReaderBuilder::new()
-                .delimiter(b'\t')
-                .from_path(&file_path)?;
+            let mut rdr = ReaderBuilder::new().delimiter(b'\t').from_path(file_path)?;
             for result in rdr.records() {
                 let record = result?;
                 data.insert(record[1].to_string(), T::from(record));
@@ -351,10 +589,7 @@ impl Contract {
     ) -> Result<(), Box<dyn Error>> {
         debug!("Entering set_external_calls");
         // Process the first CSV file
-        let loc_external_call = format!(
-            "{}{}/out/{}_ExternalCallInfo.csv",
-            TEMP_PATH, self.logic_addr, ANALYSIS
-        );
+        let loc_external_call = self.relation_path("ExternalCallInfo");
         let mut external_calls_df: Vec<ExternalCallData> = Vec::<ExternalCallData>::new();
         match fs::metadata(&loc_external_call) {
             Ok(metadata) => {
@@ -400,44 +635,44 @@ impl Contract {
             self.external_call_in_func_signature
                 .remove("__function_selector__");
         }
-        // init web3 transport
-        let transport = Web3Transport::new(&self.url).await?;
-        let contract_storage_address: Address =
-            self.storage_addr.parse().expect("Invalid contract address");
-
         for external_call_data in &external_calls_df {
             let mut external_call = ExternalCall {
                 target_logic_addr: String::new(),
                 target_storage_addr: String::new(),
                 target_func_sign: String::new(),
                 caller_addr: self.caller.clone(),
+                caller_code_key: self.caller_code_key.clone(),
                 caller_func_sign: func_sign.to_string(),
                 call_site: external_call_data.call_stmt.clone(),
+                call_stmt: external_call_data.call_stmt.clone(),
+                call_op: external_call_data.call_op.clone(),
+                resolution: CalleeResolution::Unresolved {
+                    reason: NO_CALLEE_SOURCE.to_string(),
+                },
             };
             // Logic to find and set the logic address
             if let Some(data) = self.constant_callee_df.get(&external_call_data.call_stmt) {
                 external_call.target_logic_addr =
                     data.callee.replace("000000000000000000000000", "");
+                external_call.resolution = CalleeResolution::Constant;
             }
 
             // get storage from web3 api
             if let Some(data) = self.storage_callee_df.get(&external_call_data.call_stmt) {
-                if let Some(value) = self.storage_space.get(&data.storage_slot) {
-                    external_call.target_logic_addr = value.to_string();
-                } else {
-                    external_call.target_logic_addr = transport
-                        .get_storage(
-                            contract_storage_address,
-                            &data.storage_slot,
-                            &data.byte_low,
-                            &data.byte_high,
-                        )
-                        .await?;
-                    self.storage_space.insert(
-                        data.storage_slot.clone(),
-                        external_call.target_logic_addr.clone(),
-                    );
-                }
+                let (slot, byte_low, byte_high) = (
+                    data.storage_slot.clone(),
+                    data.byte_low.clone(),
+                    data.byte_high.clone(),
+                );
+                self.set_storage_callee(
+                    &mut external_call,
+                    &slot,
+                    &byte_low,
+                    &byte_high,
+                    CalleeResolution::Storage,
+                )
+                .await;
+                self.resolve_from_constructor(&mut external_call, &slot);
             }
 
             // get storage from web3 api
@@ -445,45 +680,117 @@ impl Contract {
                 .storage_callee_proxy_df
                 .get(&external_call_data.call_stmt)
             {
-                if let Some(value) = self.storage_space.get(&data.storage_slot) {
-                    external_call.target_logic_addr = value.to_string();
+                let storage_slot = data.storage_slot.clone();
+                let layout = U256::from_str(&storage_slot)
+                    .ok()
+                    .and_then(|slot| ProxyLayout::from_slot(&slot));
+                if layout.is_some() {
+                    if let Err(e) = self
+                        .resolve_standard_proxy(&mut external_call, layout)
+                        .await
+                    {
+                        self.leave_unresolved(
+                            &mut external_call,
+                            format!("proxy lookup on {} failed: {}", self.storage_addr, e),
+                        );
+                    } else if external_call.target_logic_addr.is_empty() {
+                        external_call.resolution = Self::storage_resolution(
+                            &external_call,
+                            CalleeResolution::ProxyStorage,
+                            &storage_slot,
+                        );
+                    }
                 } else {
-                    external_call.target_logic_addr = transport
-                        .get_storage(
-                            contract_storage_address,
-                            &data.storage_slot,
-                            &String::from("0"),
-                            &String::from("19"),
-                        )
-                        .await?;
+                    self.set_storage_callee(
+                        &mut external_call,
+                        &storage_slot,
+                        "0",
+                        "19",
+                        CalleeResolution::ProxyStorage,
+                    )
+                    .await;
                 }
-                self.storage_space.insert(
-                    data.storage_slot.clone(),
-                    external_call.target_logic_addr.clone(),
-                );
+                self.resolve_from_constructor(&mut external_call, &storage_slot);
+            }
+
+            // get the mapping or array element from web3 api
+            if let Some(data) = self
+                .storage_element_callee_df
+                .get(&external_call_data.call_stmt)
+                .cloned()
+            {
+                self.resolve_storage_element(&mut external_call, &data)
+                    .await;
             }
 
             // find callee got from the func arg, and try to recover the know args
             if let Some(data) = self.func_arg_callee_df.get(&external_call_data.call_stmt) {
                 if data.func == data.pub_fun {
                     let temp_index: i32 = data.arg_index.parse::<i32>().unwrap();
-                    if self.call_arg_vals.contains_key(&temp_index) {
-                        external_call.target_logic_addr = match &self.call_arg_vals[&temp_index] {
-                            ValueType::Int(i) => i.to_string(),
-                            ValueType::Float(f) => f.to_string(),
-                            ValueType::Str(s) => s.clone(),
+                    external_call.resolution = CalleeResolution::FuncArg;
+                    if let Some(value) = self.call_arg_vals.get(&temp_index) {
+                        let target = match value {
+                            ArgValue::Int(i) => Ok(i.to_string()),
+                            ArgValue::Float(f) => Ok(f.to_string()),
+                            ArgValue::Str(s) => Ok(s.clone()),
+                            ArgValue::Env(env) => env.resolve(&self.caller_env).ok_or(env),
+                        };
+                        match target {
+                            Ok(target) => {
+                                external_call.target_logic_addr = target;
+                                external_call.resolution = CalleeResolution::KnownArg;
+                            }
+                            Err(env) => {
+                                external_call.resolution = CalleeResolution::Unresolved {
+                                    reason: format!(
+                                        "callee is {} of the caller, which is unknown",
+                                        env
+                                    ),
+                                }
+                            }
                         }
                     }
                 }
             }
 
+            // a delegate without any source may be the implementation of
+            // a standard proxy, or the facet of a diamond
+            if external_call_data.call_op == "DELEGATECALL"
+                && matches!(
+                    external_call.resolution,
+                    CalleeResolution::Unresolved { .. }
+                )
+            {
+                if let Err(e) = self.resolve_standard_proxy(&mut external_call, None).await {
+                    self.leave_unresolved(
+                        &mut external_call,
+                        format!("proxy lookup on {} failed: {}", self.storage_addr, e),
+                    );
+                }
+                if !self.origin
+                    && matches!(
+                        external_call.resolution,
+                        CalleeResolution::Unresolved { .. }
+                    )
+                {
+                    if let Err(e) = self.resolve_diamond_facet(&mut external_call).await {
+                        self.leave_unresolved(
+                            &mut external_call,
+                            format!("diamond lookup on {} failed: {}", self.storage_addr, e),
+                        );
+                    }
+                }
+            }
+
             if external_call_data.call_op == "DELEGATECALL" {
                 external_call.target_storage_addr = self.logic_addr.clone();
                 external_call.caller_addr = self.caller.clone();
+                external_call.caller_code_key = self.caller_code_key.clone();
                 external_call.call_site = self.call_site.clone();
             } else {
                 external_call.target_storage_addr = external_call.target_logic_addr.clone();
                 external_call.caller_addr = self.logic_addr.clone();
+                external_call.caller_code_key = self.code_key.clone();
                 external_call.call_site = external_call_data.call_stmt.clone();
             }
 
@@ -503,17 +810,562 @@ impl Contract {
             if let Some(data) = self.proxy_func_sign_df.get(&external_call_data.call_stmt) {
                 external_call.target_func_sign = func_sign.to_string();
             }
+            // a standard proxy or a diamond forwards its call data
+            if external_call.target_func_sign.is_empty()
+                && matches!(
+                    external_call.resolution,
+                    CalleeResolution::StandardProxy { .. } | CalleeResolution::DiamondFacet
+                )
+            {
+                external_call.target_func_sign = func_sign.to_string();
+            }
+            // the delegate of a diamond depends on the selector called
+            if self.origin
+                && external_call.call_op == "DELEGATECALL"
+                && matches!(
+                    external_call.resolution,
+                    CalleeResolution::Unresolved { .. }
+                )
+            {
+                match self.diamond_facet_calls(&external_call).await {
+                    Ok(facet_calls) if !facet_calls.is_empty() => {
+                        self.external_calls.extend(facet_calls);
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) => self.leave_unresolved(
+                        &mut external_call,
+                        format!("diamond lookup on {} failed: {}", self.storage_addr, e),
+                    ),
+                }
+            }
             self.external_calls.push(external_call);
         }
 
         Ok(())
     }
 
-    fn set_func(&mut self) -> Result<(), Box<dyn Error>> {
-        let loc = format!(
-            "{}{}/out/{}_FunctionSelector.csv",
-            TEMP_PATH, self.logic_addr, ANALYSIS
+    /// Leave `external_call` unresolved when looking up its callee failed;
+    /// the other calls of the function are still recovered
+    fn leave_unresolved(&self, external_call: &mut ExternalCall, reason: String) {
+        warn!(
+            "Failed to resolve the callee of {} in {}: {}",
+            external_call.call_stmt, self.logic_addr, reason
         );
+        external_call.target_logic_addr = String::new();
+        external_call.resolution = CalleeResolution::Unresolved { reason };
+    }
+
+    /// Delegate to the implementation behind the storage contract in the
+    /// proxy `layout`, or in the first standard layout it follows
+    async fn resolve_standard_proxy(
+        &mut self,
+        external_call: &mut ExternalCall,
+        layout: Option<ProxyLayout>,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(proxy) = self.standard_proxy(layout).await? {
+            info!(
+                "{} is a {:?} proxy of {}",
+                proxy.proxy, proxy.layout, proxy.implementation
+            );
+            external_call.target_logic_addr = proxy.implementation;
+            external_call.resolution = CalleeResolution::StandardProxy {
+                layout: proxy.layout,
+            };
+        }
+        Ok(())
+    }
+
+    /// Delegate to the facet the storage contract routes the called
+    /// selector to, when it is a diamond
+    async fn resolve_diamond_facet(
+        &mut self,
+        external_call: &mut ExternalCall,
+    ) -> Result<(), Box<dyn Error>> {
+        let selector = match diamond::parse_selector(&self.func_sign) {
+            Some(selector) => selector,
+            None => return Ok(()),
+        };
+        let storage_addr: Address = self.storage_addr.parse()?;
+        let facet = if self.is_diamond_loupe().await? {
+            diamond::facet_address(
+                self.provider.as_ref(),
+                storage_addr,
+                selector,
+                Some(self.block()),
+            )
+            .await?
+        } else {
+            diamond::mapped_facet(
+                self.provider.as_ref(),
+                storage_addr,
+                selector,
+                Some(self.block()),
+            )
+            .await?
+        };
+        if let Some(facet) = facet {
+            info!(
+                "diamond {} routes {} to {:?}",
+                self.storage_addr, self.func_sign, facet
+            );
+            external_call.target_logic_addr = format!("{:?}", facet);
+            external_call.resolution = CalleeResolution::DiamondFacet;
+        }
+        Ok(())
+    }
+
+    /// The delegatecall of a diamond's fallback to each facet, once per
+    /// selector it routes there; none when the storage contract has no loupe.
+    /// Only the fallback receives the selectors its dispatcher does not
+    /// match, which become entry functions of their own.
+    async fn diamond_facet_calls(
+        &mut self,
+        external_call: &ExternalCall,
+    ) -> Result<Vec<ExternalCall>, Box<dyn Error>> {
+        if external_call.caller_func_sign != FALLBACK_FUNC_SIGN || !self.is_diamond_loupe().await? {
+            return Ok(Vec::new());
+        }
+        if self.diamond_facets.is_none() {
+            let storage_addr: Address = self.storage_addr.parse()?;
+            self.diamond_facets = Some(
+                diamond::facets(self.provider.as_ref(), storage_addr, Some(self.block())).await?,
+            );
+        }
+        let mut dispatched: HashSet<String> = self.func_sign_dict.values().cloned().collect();
+        let mut calls = Vec::new();
+        for (facet, selectors) in self.diamond_facets.iter().flatten() {
+            for selector in selectors {
+                let func_sign = format!("0x{}", hex::encode(selector));
+                if !dispatched.insert(func_sign.clone()) {
+                    continue;
+                }
+                self.external_call_in_func_signature
+                    .insert(func_sign.clone());
+                calls.push(ExternalCall {
+                    target_logic_addr: format!("{:?}", facet),
+                    target_func_sign: func_sign.clone(),
+                    caller_func_sign: func_sign,
+                    resolution: CalleeResolution::DiamondFacet,
+                    ..external_call.clone()
+                });
+            }
+        }
+        Ok(calls)
+    }
+
+    /// Whether the storage contract is a diamond with a loupe: its own
+    /// dispatcher has the loupe functions, or it declares their interface
+    async fn is_diamond_loupe(&mut self) -> Result<bool, Box<dyn Error>> {
+        if let Some(loupe) = self.diamond_loupe {
+            return Ok(loupe);
+        }
+        let dispatched = self.logic_addr.eq_ignore_ascii_case(&self.storage_addr)
+            && self
+                .func_sign_dict
+                .values()
+                .any(|func_sign| diamond::is_loupe_function(func_sign));
+        let loupe = dispatched
+            || diamond::supports_loupe(
+                self.provider.as_ref(),
+                self.storage_addr.parse()?,
+                Some(self.block()),
+            )
+            .await;
+        self.diamond_loupe = Some(loupe);
+        Ok(loupe)
+    }
+
+    /// Contracts behind the storage contract in the proxy `layout`, or in
+    /// the first standard layout it follows. Beacons are followed to their
+    /// implementation.
+    async fn standard_proxy(
+        &mut self,
+        layout: Option<ProxyLayout>,
+    ) -> Result<Option<ResolvedProxy>, Box<dyn Error>> {
+        if let Some(proxy) = &self.proxy {
+            if layout.is_none_or(|layout| layout == proxy.layout) {
+                return Ok(Some(proxy.clone()));
+            }
+        }
+        let layouts = match layout {
+            Some(layout) => vec![layout],
+            None if self.proxy_probed => return Ok(None),
+            None => ProxyLayout::ALL.to_vec(),
+        };
+        let storage_addr: Address = self.storage_addr.parse()?;
+        let found = ProxyLayout::detect(
+            &layouts,
+            self.provider.as_ref(),
+            storage_addr,
+            Some(self.block()),
+        )
+        .await?;
+        // a probe that failed is tried again for the next call
+        self.proxy_probed |= layout.is_none();
+        Ok(found.map(|(layout, target)| {
+            let proxy = ResolvedProxy {
+                proxy: self.storage_addr.to_lowercase(),
+                layout,
+                beacon: target.beacon.map(|beacon| format!("{:?}", beacon)),
+                implementation: format!("{:?}", target.implementation),
+            };
+            self.proxy.get_or_insert(proxy.clone());
+            proxy
+        }))
+    }
+
+    /// Read the callee from the mapping or array element `data` describes,
+    /// once its key is known. An unknown key or an unreadable element leaves
+    /// this call unresolved, not the other calls of the function.
+    async fn resolve_storage_element(
+        &mut self,
+        external_call: &mut ExternalCall,
+        data: &data_structure::StorageElementCallee,
+    ) {
+        let key = match self.storage_element_key(data) {
+            Ok(key) => key,
+            Err(reason) => {
+                external_call.resolution = CalleeResolution::Unresolved { reason };
+                return;
+            }
+        };
+        let base = match U256::from_str(&data.base_slot) {
+            Ok(base) => base,
+            Err(e) => {
+                external_call.resolution = CalleeResolution::Unresolved {
+                    reason: format!("invalid base slot {}: {}", data.base_slot, e),
+                };
+                return;
+            }
+        };
+        let element = match data.kind.as_str() {
+            "mapping" => mapping_slot(key, base),
+            _ => array_element_slot(base, key, data.elem_size),
+        };
+        let slot = format!("{:#x}", element.overflowing_add(data.offset.into()).0);
+        self.set_storage_callee(
+            external_call,
+            &slot,
+            &data.byte_low,
+            &data.byte_high,
+            CalleeResolution::StorageElement,
+        )
+        .await;
+    }
+
+    /// Take the callee from `byte_low..=byte_high` of `storage_slot`, read
+    /// once per field. A field that cannot be read or decoded leaves this
+    /// call unresolved, not the other calls of the function.
+    async fn set_storage_callee(
+        &mut self,
+        external_call: &mut ExternalCall,
+        storage_slot: &str,
+        byte_low: &str,
+        byte_high: &str,
+        resolution: CalleeResolution,
+    ) {
+        let field = format!("{}[{}..={}]", storage_slot, byte_low, byte_high);
+        let value = match self.storage_space.get(&field) {
+            Some(value) => Ok(value.clone()),
+            None => {
+                let value = self
+                    .read_storage_callee(
+                        &external_call.call_stmt,
+                        storage_slot,
+                        byte_low,
+                        byte_high,
+                    )
+                    .await
+                    .map(|addr| addr.map(|addr| format!("{:?}", addr)).unwrap_or_default());
+                if let Ok(value) = &value {
+                    self.storage_space.insert(field, value.clone());
+                }
+                value
+            }
+        };
+        match value {
+            Ok(value) => {
+                external_call.target_logic_addr = value;
+                external_call.resolution =
+                    Self::storage_resolution(external_call, resolution, storage_slot);
+            }
+            Err(reason) => {
+                external_call.target_logic_addr = String::new();
+                external_call.resolution = CalleeResolution::Unresolved { reason };
+            }
+        }
+    }
+
+    /// Key of the mapping or array element holding a callee, from a
+    /// constant or an argument the caller passed a known value for
+    fn storage_element_key(
+        &self,
+        data: &data_structure::StorageElementCallee,
+    ) -> Result<U256, String> {
+        let word = |value: &str| {
+            U256::from_str(value).map_err(|e| format!("invalid {} key {}: {}", data.kind, value, e))
+        };
+        if data.key_type != "arg" {
+            return word(&data.key);
+        }
+        let unknown = || {
+            format!(
+                "{} key of the callee is argument {}, which is unknown",
+                data.kind, data.key
+            )
+        };
+        let index = data.key.parse::<i32>().map_err(|_| unknown())?;
+        match self.call_arg_vals.get(&index) {
+            Some(ArgValue::Int(i)) if *i >= 0 => Ok(U256::from(*i)),
+            Some(ArgValue::Str(s)) => word(s),
+            Some(ArgValue::Env(env)) => env
+                .resolve(&self.caller_env)
+                .and_then(|addr| addr.parse::<Address>().ok())
+                .map(|addr| U256::from_big_endian(addr.as_bytes()))
+                .ok_or_else(unknown),
+            _ => Err(unknown()),
+        }
+    }
+
+    /// `resolution` for a callee read from `storage_slot`, unless the slot
+    /// could not be read
+    fn storage_resolution(
+        external_call: &ExternalCall,
+        resolution: CalleeResolution,
+        storage_slot: &str,
+    ) -> CalleeResolution {
+        if external_call.target_logic_addr.is_empty() {
+            CalleeResolution::Unresolved {
+                reason: format!("storage slot {} is unavailable", storage_slot),
+            }
+        } else {
+            resolution
+        }
+    }
+
+    /// Take the callee from the constructor's write to `storage_slot` when
+    /// the slot is empty on chain, e.g. because the contract is not deployed
+    /// yet at the snapshot block
+    fn resolve_from_constructor(&self, external_call: &mut ExternalCall, storage_slot: &str) {
+        let target = external_call.target_logic_addr.trim_start_matches("0x");
+        if !target.chars().all(|c| c == '0') {
+            return;
+        }
+        if let Some(addr) = self.constructor_storage.get(storage_slot) {
+            external_call.target_logic_addr = addr.clone();
+            external_call.resolution = CalleeResolution::ConstructorStorage;
+        }
+    }
+
+    /// Addresses the constructor writes to storage, by slot. Slots written
+    /// with different addresses are left out.
+    pub fn constructor_storage_writes(&self) -> Result<HashMap<String, String>, Box<dyn Error>> {
+        let mut writes: HashMap<String, Option<String>> = HashMap::new();
+        let loc = self.relation_path("StorageWrite_ConstType");
+        if fs::metadata(&loc).map(|m| m.len() > 0).unwrap_or(false) {
+            let mut rdr = ReaderBuilder::new()
+                .delimiter(b'\t')
+                .has_headers(false)
+                .from_path(loc)?;
+            for result in rdr.deserialize::<(String, String, String, u32, u32, String)>() {
+                let (_func, _store, slot, byte_low, byte_high, value) = result?;
+                // only address-sized fields
+                if byte_high + 1 != byte_low + 20 {
+                    continue;
+                }
+                let value = value.trim_start_matches("0x").to_lowercase();
+                let addr = format!("0x{:0>40}", &value[value.len().saturating_sub(40)..]);
+                writes
+                    .entry(slot)
+                    .and_modify(|known| {
+                        if known.as_deref() != Some(addr.as_str()) {
+                            *known = None;
+                        }
+                    })
+                    .or_insert_with(|| Some(addr.clone()));
+            }
+        }
+        Ok(writes
+            .into_iter()
+            .filter_map(|(slot, addr)| Some((slot, addr?)))
+            .collect())
+    }
+
+    /// Recover the contracts created with init code copied from the own
+    /// bytecode. Their address follows from a constant salt for CREATE2,
+    /// and for CREATE only from a constructor with a single creation, which
+    /// runs at nonce 1 (EIP-161). The init code of a contract whose address
+    /// is known is kept in the createbin folder to be analyzed.
+    fn set_created_contracts(&mut self) -> Result<(), Box<dyn Error>> {
+        let init_codes: Vec<(String, String, String, String, String)> =
+            Self::read_rows(&self.relation_path("Create_InitCode"))?;
+        if init_codes.is_empty() {
+            return Ok(());
+        }
+        let lengths: HashMap<String, String> =
+            Self::read_rows(&self.relation_path("Create_ConstLength"))?
+                .into_iter()
+                .collect();
+        let salts: HashMap<String, String> =
+            Self::read_rows(&self.relation_path("Create2_ConstSalt"))?
+                .into_iter()
+                .collect();
+        let sole_creation = self.createbin && self.creates_once()?;
+        let code = decode_hex(&fs::read_to_string(self.bytecode_path())?)?;
+        let creator: Address = self.storage_addr.parse()?;
+
+        let mut seen = HashSet::new();
+        for (func_sign, stmt, opcode, code_offset, code_length) in init_codes {
+            if (!self.origin && func_sign != self.func_sign) || !seen.insert(stmt.clone()) {
+                continue;
+            }
+            let init_code = match Self::value_to_usize(&code_offset)
+                .zip(Self::value_to_usize(&code_length))
+                .and_then(|(offset, length)| code.get(offset..offset.checked_add(length)?))
+            {
+                Some(init_code) => init_code,
+                None => {
+                    warn!(
+                        "init code of {} at {} is out of the bytecode of {}",
+                        opcode, stmt, self.logic_addr
+                    );
+                    continue;
+                }
+            };
+            // the init code is complete when nothing is appended to it
+            let complete = lengths
+                .get(&stmt)
+                .and_then(|length| Self::value_to_usize(length))
+                == Some(init_code.len());
+            let salt = salts.get(&stmt).and_then(|salt| {
+                U256::from_str(salt)
+                    .ok()
+                    .map(|salt| H256::from(<[u8; 32]>::from(salt)))
+            });
+            let address = match opcode.as_str() {
+                "CREATE2" if complete => {
+                    salt.map(|salt| create2_address(&creator, &salt, init_code))
+                }
+                "CREATE" if sole_creation => Some(create_address(&creator, 1)),
+                _ => None,
+            }
+            .map(|address| format!("{:?}", address));
+            if let Some(address) = &address {
+                self.workspace.write_createbin(address, init_code)?;
+            }
+            info!(
+                "{} creates {:?} at {} with {}",
+                self.logic_addr, address, stmt, opcode
+            );
+            self.created_contracts.push(CreatedContract {
+                creator: self.storage_addr.clone(),
+                func_sign,
+                create_site: stmt,
+                opcode,
+                address,
+                salt: salt.map(|salt| format!("{:?}", salt)),
+                code_key: Workspace::code_key(init_code),
+                semantic_features: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether the constructor has a single creation outside of loops
+    fn creates_once(&self) -> Result<bool, Box<dyn Error>> {
+        let in_loop: Vec<(String, String)> =
+            Self::read_rows(&self.relation_path("Op_CreateInLoop"))?;
+        let sole: HashSet<String> =
+            Self::read_rows::<(String, String)>(&self.relation_path("Op_SoleCreate"))?
+                .into_iter()
+                .map(|(_, stmt)| stmt)
+                .collect();
+        Ok(in_loop.is_empty() && sole.len() == 1)
+    }
+
+    /// Rows of a headerless relation, none when Gigahorse did not output it
+    fn read_rows<T: serde::de::DeserializeOwned>(loc: &str) -> Result<Vec<T>, Box<dyn Error>> {
+        if !fs::metadata(loc).map(|m| m.len() > 0).unwrap_or(false) {
+            return Ok(Vec::new());
+        }
+        let mut rdr = ReaderBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(false)
+            .from_path(loc)?;
+        Ok(rdr.deserialize().collect::<Result<_, _>>()?)
+    }
+
+    fn value_to_usize(value: &str) -> Option<usize> {
+        usize::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+    }
+
+    /// External calls the call graph cannot follow, because their target
+    /// address or function selector is unknown
+    pub fn unresolved_calls(&self) -> Vec<UnresolvedCall> {
+        self.external_calls
+            .iter()
+            .filter_map(|external_call| {
+                let reason = if external_call.target_logic_addr.is_empty() {
+                    external_call.resolution.missing_target_reason()
+                } else if external_call.target_func_sign.is_empty() {
+                    "function selector is unknown".to_string()
+                } else {
+                    return None;
+                };
+                Some(UnresolvedCall {
+                    func_sign: external_call.caller_func_sign.clone(),
+                    call_site: external_call.call_stmt.clone(),
+                    call_op: external_call.call_op.clone(),
+                    resolution: external_call.resolution.clone(),
+                    reason,
+                })
+            })
+            .collect()
+    }
+
+    /// Read the callee address packed in a storage slot of the storage
+    /// contract; slots the provider does not have are recorded as unresolved
+    /// and read as `None`, other failures are the reason the callee is unknown
+    async fn read_storage_callee(
+        &mut self,
+        call_stmt: &str,
+        storage_slot: &str,
+        byte_low: &str,
+        byte_high: &str,
+    ) -> Result<Option<Address>, String> {
+        let contract_storage_address: Address = self
+            .storage_addr
+            .parse()
+            .map_err(|e| format!("invalid storage address {}: {}", self.storage_addr, e))?;
+        let slot = U256::from_str(storage_slot)
+            .map_err(|e| format!("invalid storage slot {}: {}", storage_slot, e))?;
+        match self
+            .provider
+            .get_storage(contract_storage_address, slot, Some(self.block()))
+            .await
+        {
+            Ok(word) => storage_word_to_addr(word, byte_low, byte_high)
+                .map(Some)
+                .map_err(|e| format!("storage slot {}: {}", storage_slot, e)),
+            Err(LydiaError::StateUnavailable(reason)) => {
+                self.unresolved_callees.push(UnresolvedCallee {
+                    caller: self.logic_addr.clone(),
+                    call_site: call_stmt.to_string(),
+                    target: self.storage_addr.clone(),
+                    reason,
+                });
+                Ok(None)
+            }
+            Err(e) => Err(format!(
+                "storage slot {} could not be read: {}",
+                storage_slot, e
+            )),
+        }
+    }
+
+    fn set_func(&mut self) -> Result<(), Box<dyn Error>> {
+        let loc = self.relation_path("FunctionSelector");
 
         if fs::metadata(&loc).map(|m| m.len() > 0).unwrap_or(false) {
             let mut rdr = ReaderBuilder::new().delimiter(b'\t').from_path(loc)?;
@@ -548,7 +1400,7 @@ impl Contract {
                         .func_sign_dict
                         .iter()
                         .find_map(|(key, val)| {
-                            if val == &String::from("0x00000000") {
+                            if val == FALLBACK_FUNC_SIGN {
                                 Some(key.clone())
                             } else {
                                 None
@@ -562,13 +1414,34 @@ impl Contract {
         Ok(())
     }
 
+    /// Textual signatures Gigahorse recovered for the function selectors
+    fn set_func_names(&mut self) -> Result<(), Box<dyn Error>> {
+        let loc = self.relation_path("FunctionInfo");
+        if fs::metadata(&loc).map(|m| m.len() > 0).unwrap_or(false) {
+            let mut rdr = ReaderBuilder::new()
+                .delimiter(b'\t')
+                .has_headers(false)
+                .from_path(loc)?;
+            for result in rdr.deserialize::<(String, String, String)>() {
+                let (_func, func_sign, name) = result?;
+                // unknown signatures are named after the selector or function id
+                if !name.starts_with("0x") && name != func_sign {
+                    self.func_names.insert(func_sign, name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// High-level name of the function with selector `func_sign`, when known
+    pub fn func_name(&self, func_sign: &str) -> Option<&str> {
+        self.func_names.get(func_sign).map(String::as_str)
+    }
+
     // add env var as the known call arg values
     fn set_call_arg_vals(&mut self) -> Result<(), Box<dyn Error>> {
-        if !self.caller.is_empty() {
-            let loc = format!(
-                "{}{}/out/{}_ExternalCall_Known_Arg.csv",
-                TEMP_PATH, self.caller, ANALYSIS
-            );
+        if !self.caller_code_key.is_empty() {
+            let loc = self.relation_path_of(&self.caller_code_key, "ExternalCall_Known_Arg");
 
             if fs::metadata(&loc).map(|m| m.len() > 0).unwrap_or(false) {
                 let mut rdr = ReaderBuilder::new().delimiter(b'\t').from_path(loc)?;
@@ -578,11 +1451,11 @@ impl Contract {
 
                     if call_stmt == self.call_site {
                         let value = if let Ok(int_val) = arg_val_str.parse::<i32>() {
-                            ValueType::Int(int_val)
+                            ArgValue::Int(int_val)
                         } else if let Ok(float_val) = arg_val_str.parse::<f64>() {
-                            ValueType::Float(float_val)
+                            ArgValue::Float(float_val)
                         } else {
-                            ValueType::Str(arg_val_str)
+                            ArgValue::Str(arg_val_str)
                         };
 
                         self.call_arg_vals.insert(arg_index, value);
@@ -590,7 +1463,749 @@ impl Contract {
                     }
                 }
             }
+            self.set_env_arg_vals()?;
+        }
+        Ok(())
+    }
+
+    // add msg.sender, tx.origin and address(this) of the caller as symbolic
+    // call arg values
+    fn set_env_arg_vals(&mut self) -> Result<(), Box<dyn Error>> {
+        let loc = self.relation_path_of(&self.caller_code_key, "ExternalCall_Known_Arg_Env");
+        if fs::metadata(&loc).map(|m| m.len() > 0).unwrap_or(false) {
+            let mut rdr = ReaderBuilder::new()
+                .delimiter(b'\t')
+                .has_headers(false)
+                .from_path(loc)?;
+            for result in rdr.deserialize::<(String, String, i32, String)>() {
+                let (_func, call_stmt, arg_index, opcode) = result?;
+                if call_stmt != self.call_site {
+                    continue;
+                }
+                if let Some(env) = EnvValue::from_opcode(&opcode) {
+                    self.call_arg_vals.insert(arg_index, ArgValue::Env(env));
+                }
+            }
+            info!("call arg values: {:?}", self.call_arg_vals);
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+impl Contract {
+    /// Contract of `source` as if its code, the creation code of a
+    /// createbin source, had been analyzed under `code_key`, with the given
+    /// calls
+    pub(crate) fn analyzed_for_test(
+        source: &AnalysisSource,
+        code_key: &str,
+        external_calls: Vec<ExternalCall>,
+    ) -> Self {
+        Self {
+            code_key: code_key.to_string(),
+            createbin: source.createbin,
+            external_calls,
+            ..Self::from_source(source)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+    use crate::workspace::TestWorkspace;
+    use web3::ethabi::Token;
+
+    /// EIP-1167 clone of 0xbebe..be, delegating at 0x1f
+    const CLONE_CODE: &str = "363d3d373d3d3d363d73bebebebebebebebebebebebebebebebebebebebe\
+                              5af43d82803e903d91602b57fd5bf3";
+
+    /// Source of an entry function of `logic_addr` reading state from `provider`
+    fn source_with(logic_addr: &str, provider: MockProvider) -> AnalysisSource {
+        let mut source = AnalysisSource::for_test(logic_addr);
+        source.provider = Arc::new(provider);
+        source
+    }
+
+    /// Same, with a workspace of its own that lives as long as the returned
+    /// guard
+    fn source_in_workspace(
+        name: &str,
+        logic_addr: &str,
+        provider: MockProvider,
+    ) -> (TestWorkspace, AnalysisSource) {
+        let test = TestWorkspace::new(name);
+        fs::create_dir_all(&test.workspace.contracts_dir).unwrap();
+        let mut source = source_with(logic_addr, provider);
+        source.workspace = test.workspace.clone();
+        (test, source)
+    }
+
+    /// Leslie relations of `code_key`, as if Gigahorse had just run on it
+    fn write_relations(workspace: &Workspace, code_key: &str, relations: &[(&str, &str)]) {
+        let out_dir = workspace.scratch_out_dir(code_key);
+        fs::create_dir_all(&out_dir).unwrap();
+        for (relation, rows) in relations {
+            fs::write(out_dir.join(format!("Leslie_{}.csv", relation)), rows).unwrap();
+        }
+    }
+
+    fn unresolved() -> CalleeResolution {
+        CalleeResolution::Unresolved {
+            reason: NO_CALLEE_SOURCE.to_string(),
+        }
+    }
+
+    fn external_call(target: &str, func_sign: &str, resolution: CalleeResolution) -> ExternalCall {
+        ExternalCall {
+            target_logic_addr: target.to_string(),
+            target_storage_addr: target.to_string(),
+            target_func_sign: func_sign.to_string(),
+            call_site: "0x1a".to_string(),
+            caller_func_sign: "0x12345678".to_string(),
+            caller_addr: "0x0000000000000000000000000000000000000001".to_string(),
+            caller_code_key: String::new(),
+            call_stmt: "0x1a".to_string(),
+            call_op: "CALL".to_string(),
+            resolution,
+        }
+    }
+
+    #[test]
+    fn test_unresolved_calls() {
+        let mut contract = Contract::from_source(&AnalysisSource::for_test(
+            "0x0000000000000000000000000000000000000001",
+        ));
+        let callee = "0x0000000000000000000000000000000000000002";
+        contract.external_calls = vec![
+            external_call(callee, "0xa9059cbb", CalleeResolution::Constant),
+            external_call("", "0xa9059cbb", CalleeResolution::FuncArg),
+            external_call(callee, "", CalleeResolution::Storage),
+            external_call(
+                "",
+                "",
+                CalleeResolution::Unresolved {
+                    reason: "storage slot 0x0 is unavailable".to_string(),
+                },
+            ),
+        ];
+
+        let reasons: Vec<_> = contract
+            .unresolved_calls()
+            .into_iter()
+            .map(|call| call.reason)
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "callee is a function argument without a known value",
+                "function selector is unknown",
+                "storage slot 0x0 is unavailable",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_env_arg_resolution() {
+        let env = CallerEnv {
+            sender: "0x00000000000000000000000000000000000000AA".to_string(),
+            address: "0x0000000000000000000000000000000000000001".to_string(),
+            origin: String::new(),
+        };
+        assert_eq!(EnvValue::from_opcode("CALLER"), Some(EnvValue::Caller));
+        assert_eq!(EnvValue::from_opcode("CALLVALUE"), None);
+        assert_eq!(
+            EnvValue::Caller.resolve(&env).as_deref(),
+            Some("0x00000000000000000000000000000000000000aa")
+        );
+        assert_eq!(
+            EnvValue::This.resolve(&env).as_deref(),
+            Some("0x0000000000000000000000000000000000000001")
+        );
+        // the origin of the transaction is not configured
+        assert_eq!(EnvValue::Origin.resolve(&env), None);
+        assert_eq!(EnvValue::Origin.to_string(), "tx.origin");
+    }
+
+    #[test]
+    fn test_constructor_storage() {
+        let (_workspace, mut source) = source_in_workspace(
+            "ctor-storage",
+            "0x0000000000000000000000000000000000000001",
+            MockProvider::new(),
+        );
+        let mut constructor = Contract::from_source(&source);
+        constructor.code_key = "aa".to_string();
+        write_relations(
+            &source.workspace,
+            "aa",
+            &[(
+                "StorageWrite_ConstType",
+                "0x1\t0x10\t0x0\t0\t19\t0xBB4CDB9CBD36B01BD1CBAEBF2DE08D9173BC095C\n\
+                 0x1\t0x11\t0x1\t0\t31\t0x64\n\
+                 0x1\t0x12\t0x2\t0\t19\t0x1234\n\
+                 0x1\t0x13\t0x2\t0\t19\t0x5678\n",
+            )],
+        );
+        let writes = constructor.constructor_storage_writes().unwrap();
+        // the uint in slot 1 and the ambiguous slot 2 are left out
+        assert_eq!(
+            writes,
+            HashMap::from([(
+                "0x0".to_string(),
+                "0xbb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c".to_string()
+            )])
+        );
+
+        source.constructor_storage = Arc::new(writes);
+        let runtime = Contract::from_source(&source);
+        let mut empty_slot = external_call(
+            "0x0000000000000000000000000000000000000000",
+            "0xa9059cbb",
+            CalleeResolution::Storage,
+        );
+        runtime.resolve_from_constructor(&mut empty_slot, "0x0");
+        assert_eq!(
+            empty_slot.target_logic_addr,
+            "0xbb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c"
+        );
+        assert_eq!(empty_slot.resolution, CalleeResolution::ConstructorStorage);
+        // storage set on chain takes precedence
+        let callee = "0x0000000000000000000000000000000000000002";
+        let mut set_slot = external_call(callee, "0xa9059cbb", CalleeResolution::Storage);
+        runtime.resolve_from_constructor(&mut set_slot, "0x0");
+        assert_eq!(set_slot.target_logic_addr, callee);
+    }
+
+    #[test]
+    fn test_created_contracts() {
+        let creator = "0x00000000000000000000000000000000deadbeef";
+        let (_workspace, source) = source_in_workspace("created", creator, MockProvider::new());
+
+        // runtime code embedding the init code 0xdeadbeef
+        let mut runtime = Contract::from_source(&source);
+        runtime.code_key = source
+            .workspace
+            .write_bytecode(&decode_hex("6080604052deadbeef").unwrap())
+            .unwrap();
+        write_relations(
+            &source.workspace,
+            &runtime.code_key,
+            &[
+                (
+                    "Create_InitCode",
+                    "0x12345678\t0x10\tCREATE2\t0x5\t0x4\n\
+                     0x12345678\t0x20\tCREATE2\t0x5\t0x4\n\
+                     0x12345678\t0x30\tCREATE\t0x5\t0x4\n\
+                     0x12345678\t0x40\tCREATE\t0x8\t0x4\n\
+                     0x87654321\t0x50\tCREATE\t0x5\t0x4\n",
+                ),
+                ("Create_ConstLength", "0x10\t0x4\n0x20\t0x24\n"),
+                ("Create2_ConstSalt", "0x10\t0xcafebabe\n0x20\t0xcafebabe\n"),
+            ],
+        );
+        runtime.set_created_contracts().unwrap();
+        let created: Vec<_> = runtime
+            .created_contracts
+            .iter()
+            .map(|created| (created.create_site.as_str(), created.address.as_deref()))
+            .collect();
+        // constructor arguments are appended at 0x20, the nonce of the
+        // creator is unknown at 0x30 and the code at 0x40 is out of range
+        assert_eq!(
+            created,
+            vec![
+                ("0x10", Some("0x60f3f640a8508fc6a86d45df051962668e1e8ac7")),
+                ("0x20", None),
+                ("0x30", None),
+            ]
+        );
+        let init_code = decode_hex("deadbeef").unwrap();
+        assert_eq!(
+            runtime.created_contracts[0].code_key,
+            Workspace::code_key(&init_code)
+        );
+        assert_eq!(
+            fs::read_to_string(
+                source
+                    .workspace
+                    .createbin_path("0x60f3f640a8508fc6a86d45df051962668e1e8ac7")
+            )
+            .unwrap(),
+            "deadbeef"
+        );
+
+        // a constructor creating a single contract does so at nonce 1
+        let mut constructor = Contract::from_source(&source);
+        constructor.createbin = true;
+        constructor.code_key = source
+            .workspace
+            .write_bytecode(&decode_hex("60806040deadbeef").unwrap())
+            .unwrap();
+        write_relations(
+            &source.workspace,
+            &constructor.code_key,
+            &[
+                ("Create_InitCode", "0x12345678\t0x30\tCREATE\t0x4\t0x4\n"),
+                ("Op_SoleCreate", "0x12345678\t0x30\n"),
+            ],
+        );
+        constructor.set_created_contracts().unwrap();
+        let deployer: Address = creator.parse().unwrap();
+        assert_eq!(
+            constructor.created_contracts[0].address,
+            Some(format!("{:?}", create_address(&deployer, 1)))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_standard_proxy_resolution() {
+        let proxy = "0x0000000000000000000000000000000000000001";
+        let implementation = Address::from_low_u64_be(0x10c1c);
+        let mut provider = MockProvider::new();
+        provider.set_storage(
+            proxy.parse().unwrap(),
+            ProxyLayout::Eip1967.slot(),
+            H256::from(implementation),
+        );
+        let mut contract = Contract::from_source(&source_with(proxy, provider));
+
+        let mut delegate = external_call("", "", unresolved());
+        contract
+            .resolve_standard_proxy(&mut delegate, None)
+            .await
+            .unwrap();
+        assert_eq!(delegate.target_logic_addr, format!("{:?}", implementation));
+        assert_eq!(
+            delegate.resolution,
+            CalleeResolution::StandardProxy {
+                layout: ProxyLayout::Eip1967
+            }
+        );
+        assert_eq!(
+            contract.proxy,
+            Some(ResolvedProxy {
+                proxy: proxy.to_string(),
+                layout: ProxyLayout::Eip1967,
+                beacon: None,
+                implementation: format!("{:?}", implementation),
+            })
+        );
+        // the slot of a UUPS proxy is not set
+        let mut uups = external_call("", "", CalleeResolution::ProxyStorage);
+        contract
+            .resolve_standard_proxy(&mut uups, Some(ProxyLayout::Eip1822))
+            .await
+            .unwrap();
+        assert!(uups.target_logic_addr.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_minimal_proxy_is_not_decompiled() {
+        let clone = "0x0000000000000000000000000000000000000001";
+        let mut provider = MockProvider::new();
+        provider.set_code(clone.parse().unwrap(), decode_hex(CLONE_CODE).unwrap());
+        let (_workspace, source) = source_in_workspace("clone", clone, provider);
+        let mut contract = Contract::from_source(&source);
+        // the workspace has no Gigahorse to decompile with
+        contract.analyze().await.unwrap();
+
+        assert_eq!(contract.external_calls.len(), 1);
+        let delegate = &contract.external_calls[0];
+        assert_eq!(
+            delegate.target_logic_addr,
+            "0xbebebebebebebebebebebebebebebebebebebebe"
+        );
+        assert_eq!(delegate.target_storage_addr, clone);
+        assert_eq!(delegate.target_func_sign, "0x12345678");
+        assert_eq!(delegate.call_op, "DELEGATECALL");
+        assert_eq!(delegate.call_stmt, "0x1f");
+        assert_eq!(
+            delegate.resolution,
+            CalleeResolution::MinimalProxy {
+                kind: MinimalProxy::Eip1167
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_origin_clone_delegates_public_functions() {
+        let clone = "0x0000000000000000000000000000000000000001";
+        let implementation_code = decode_hex("6080604052").unwrap();
+        let mut provider = MockProvider::new();
+        provider.set_code(clone.parse().unwrap(), decode_hex(CLONE_CODE).unwrap());
+        provider.set_code(
+            "0xbebebebebebebebebebebebebebebebebebebebe"
+                .parse()
+                .unwrap(),
+            implementation_code.clone(),
+        );
+        let (_workspace, mut source) = source_in_workspace("origin-clone", clone, provider);
+        source.func_sign = String::new();
+        // the implementation was decompiled before
+        let out_dir = source
+            .workspace
+            .decompiled_out_dir(&Workspace::code_key(&implementation_code));
+        fs::create_dir_all(&out_dir).unwrap();
+        fs::write(
+            out_dir.join("Leslie_FunctionSelector.csv"),
+            "0x1\t0xa9059cbb\n0x2\t0x095ea7b3\n0x3\t__function_selector__\n",
+        )
+        .unwrap();
+        let mut contract = Contract::from_source(&source);
+        contract.analyze().await.unwrap();
+
+        assert_eq!(contract.func_sign_list, vec!["0x095ea7b3", "0xa9059cbb"]);
+        assert_eq!(contract.external_calls.len(), 2);
+        for (delegate, func_sign) in contract.external_calls.iter().zip(&contract.func_sign_list) {
+            assert_eq!(
+                delegate.target_logic_addr,
+                "0xbebebebebebebebebebebebebebebebebebebebe"
+            );
+            assert_eq!(delegate.target_storage_addr, clone);
+            assert_eq!(&delegate.caller_func_sign, func_sign);
+            assert_eq!(&delegate.target_func_sign, func_sign);
+            assert!(contract.external_call_in_func_signature.contains(func_sign));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_proxy_lookup_failure_keeps_other_calls() {
+        let proxy = "0x0000000000000000000000000000000000000001";
+        let mut provider = MockProvider::new();
+        provider.set_unreachable(proxy.parse().unwrap());
+        let (_workspace, mut source) = source_in_workspace("proxy-failure", proxy, provider);
+        // from the origin, a delegate is not looked up in a diamond loupe
+        source.func_sign = String::new();
+        let mut contract = Contract::from_source(&source);
+        contract.code_key = "aa".to_string();
+        write_relations(
+            &source.workspace,
+            "aa",
+            &[
+                (
+                    "ExternalCallInfo",
+                    "0x1\t0x10\tDELEGATECALL\tv1\t0\t0\n\
+                     0x1\t0x20\tDELEGATECALL\tv2\t0\t0\n\
+                     0x1\t0x30\tCALL\tv3\t0\t0\n",
+                ),
+                (
+                    "ExternalCall_Callee_StorageType_ForProxy",
+                    &format!(
+                        "func\tcall_stmt\tstorage_slot\n0x1\t0x10\t{:#x}\n",
+                        ProxyLayout::Eip1967.slot()
+                    ),
+                ),
+                (
+                    "ExternalCall_Callee_ConstType",
+                    "func\tcall_stmt\tcallee\n\
+                     0x1\t0x30\t0x0000000000000000000000000000000000000002\n",
+                ),
+            ],
+        );
+        contract.set_call_info();
+        contract
+            .set_external_calls("0x1", "0x12345678")
+            .await
+            .unwrap();
+
+        let resolutions: Vec<_> = contract
+            .external_calls
+            .iter()
+            .map(|call| (call.call_stmt.as_str(), call.resolution.clone()))
+            .collect();
+        let failed = CalleeResolution::Unresolved {
+            reason: format!(
+                "proxy lookup on {} failed: Network error: {:?} is unreachable",
+                proxy,
+                proxy.parse::<Address>().unwrap()
+            ),
+        };
+        assert_eq!(
+            resolutions,
+            vec![
+                ("0x10", failed.clone()),
+                ("0x20", failed),
+                ("0x30", CalleeResolution::Constant),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_diamond_lookup_failure_keeps_other_calls() {
+        let diamond = "0x0000000000000000000000000000000000000001";
+        let mut provider = MockProvider::new();
+        provider.set_unreachable(diamond.parse().unwrap());
+        let (_workspace, source) = source_in_workspace("diamond-failure", diamond, provider);
+        let mut contract = Contract::from_source(&source);
+        contract.code_key = "aa".to_string();
+        write_relations(
+            &source.workspace,
+            "aa",
+            &[
+                (
+                    "ExternalCallInfo",
+                    "0x1\t0x10\tDELEGATECALL\tv1\t0\t0\n\
+                     0x1\t0x30\tCALL\tv3\t0\t0\n",
+                ),
+                (
+                    "ExternalCall_Callee_ConstType",
+                    "func\tcall_stmt\tcallee\n\
+                     0x1\t0x30\t0x0000000000000000000000000000000000000002\n",
+                ),
+            ],
+        );
+        contract.set_call_info();
+        contract
+            .set_external_calls("0x1", "0x12345678")
+            .await
+            .unwrap();
+
+        assert_eq!(contract.external_calls.len(), 2);
+        assert!(matches!(
+            &contract.external_calls[0].resolution,
+            CalleeResolution::Unresolved { reason } if reason.starts_with("diamond lookup on")
+        ));
+        assert_eq!(
+            contract.external_calls[1].resolution,
+            CalleeResolution::Constant
+        );
+    }
+
+    #[tokio::test]
+    async fn test_storage_callee_fields() {
+        let contract_addr = "0x0000000000000000000000000000000000000001";
+        let callee = Address::from_low_u64_be(0xca11);
+        let mut provider = MockProvider::new();
+        provider.set_storage(
+            contract_addr.parse().unwrap(),
+            U256::zero(),
+            H256::from(callee),
+        );
+        let mut contract = Contract::from_source(&source_with(contract_addr, provider));
+
+        // an unpacked address variable, reported over the whole slot
+        let mut unpacked = external_call("", "0xa9059cbb", CalleeResolution::Storage);
+        contract
+            .set_storage_callee(&mut unpacked, "0x0", "0", "31", CalleeResolution::Storage)
+            .await;
+        assert_eq!(unpacked.target_logic_addr, format!("{:?}", callee));
+        assert_eq!(unpacked.resolution, CalleeResolution::Storage);
+        // a field too narrow for an address only leaves its own call unresolved
+        let mut narrow = external_call("", "0xa9059cbb", CalleeResolution::Storage);
+        contract
+            .set_storage_callee(&mut narrow, "0x0", "0", "7", CalleeResolution::Storage)
+            .await;
+        assert!(narrow.target_logic_addr.is_empty());
+        assert_eq!(
+            narrow.resolution,
+            CalleeResolution::Unresolved {
+                reason: "storage slot 0x0: Field of 8 bytes decoded as a 20-byte value".to_string()
+            }
+        );
+        let mut packed = external_call("", "0xa9059cbb", CalleeResolution::Storage);
+        contract
+            .set_storage_callee(&mut packed, "0x0", "0", "19", CalleeResolution::Storage)
+            .await;
+        assert_eq!(packed.target_logic_addr, format!("{:?}", callee));
+    }
+
+    #[tokio::test]
+    async fn test_storage_element_resolution() {
+        let contract_addr = "0x0000000000000000000000000000000000000001";
+        let pool = Address::from_low_u64_be(0x9001);
+        let router = Address::from_low_u64_be(0x9002);
+        let token: Address = "0x00000000000000000000000000000000000000aa"
+            .parse()
+            .unwrap();
+        let mut provider = MockProvider::new();
+        // mapping(address => address) pools at slot 3
+        provider.set_storage(
+            contract_addr.parse().unwrap(),
+            mapping_slot(U256::from_big_endian(token.as_bytes()), U256::from(3)),
+            H256::from(pool),
+        );
+        // struct { uint256 fee; address router; }[] at slot 4
+        provider.set_storage(
+            contract_addr.parse().unwrap(),
+            array_element_slot(U256::from(4), U256::from(1), 2) + U256::one(),
+            H256::from(router),
+        );
+        let mut contract = Contract::from_source(&source_with(contract_addr, provider));
+        contract
+            .call_arg_vals
+            .insert(0, ArgValue::Str(format!("{:?}", token)));
+
+        let element = |kind: &str, base_slot: &str, key_type: &str, key: &str, offset| {
+            data_structure::StorageElementCallee {
+                func: "0x1".to_string(),
+                call_stmt: "0x1a".to_string(),
+                kind: kind.to_string(),
+                base_slot: base_slot.to_string(),
+                key_type: key_type.to_string(),
+                key: key.to_string(),
+                elem_size: if kind == "array" { 2 } else { 1 },
+                offset,
+                byte_low: "0".to_string(),
+                byte_high: "19".to_string(),
+            }
+        };
+        let mut calls = Vec::new();
+        for data in [
+            // pools[token] with the token passed by the caller
+            element("mapping", "0x3", "arg", "0", 0),
+            // pools[0xaa] with a constant key
+            element("mapping", "0x3", "const", "0xaa", 0),
+            // routes[1].router
+            element("array", "0x4", "const", "0x1", 1),
+            // the caller passed nothing for the second argument
+            element("mapping", "0x3", "arg", "1", 0),
+        ] {
+            let mut call = external_call("", "0xa9059cbb", unresolved());
+            contract.resolve_storage_element(&mut call, &data).await;
+            calls.push(call);
+        }
+
+        assert_eq!(calls[0].target_logic_addr, format!("{:?}", pool));
+        assert_eq!(calls[0].resolution, CalleeResolution::StorageElement);
+        assert_eq!(calls[1].target_logic_addr, format!("{:?}", pool));
+        assert_eq!(calls[2].target_logic_addr, format!("{:?}", router));
+        assert_eq!(calls[2].resolution, CalleeResolution::StorageElement);
+        assert!(calls[3].target_logic_addr.is_empty());
+        assert_eq!(
+            calls[3].resolution,
+            CalleeResolution::Unresolved {
+                reason: "mapping key of the callee is argument 1, which is unknown".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_storage_element_read_failure() {
+        let contract_addr = "0x0000000000000000000000000000000000000001";
+        let mut provider = MockProvider::new();
+        provider.set_unreachable(contract_addr.parse().unwrap());
+        let mut contract = Contract::from_source(&source_with(contract_addr, provider));
+        let data = data_structure::StorageElementCallee {
+            func: "0x1".to_string(),
+            call_stmt: "0x1a".to_string(),
+            kind: "mapping".to_string(),
+            base_slot: "0x3".to_string(),
+            key_type: "const".to_string(),
+            key: "0xaa".to_string(),
+            elem_size: 1,
+            offset: 0,
+            byte_low: "0".to_string(),
+            byte_high: "19".to_string(),
+        };
+        let mut call = external_call("", "0xa9059cbb", unresolved());
+        contract.resolve_storage_element(&mut call, &data).await;
+
+        assert!(call.target_logic_addr.is_empty());
+        match &call.resolution {
+            CalleeResolution::Unresolved { reason } => {
+                assert!(reason.contains("unreachable"), "{}", reason)
+            }
+            other => panic!("unexpected resolution {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_diamond_facet_resolution() {
+        let diamond = "0x0000000000000000000000000000000000000001";
+        let not_diamond = "0x0000000000000000000000000000000000000002";
+        let facet = Address::from_low_u64_be(0xfac1);
+        let mut provider = MockProvider::new();
+        // supportsInterface(IDiamondLoupe)
+        provider.set_call_result(
+            diamond.parse().unwrap(),
+            decode_hex(&format!("01ffc9a748e2b093{}", "0".repeat(56))).unwrap(),
+            H256::from_low_u64_be(1).as_bytes().to_vec(),
+        );
+        // facetAddress(0x12345678), also answered by a plain fallback
+        for contract in [diamond, not_diamond] {
+            provider.set_call_result(
+                contract.parse().unwrap(),
+                decode_hex(&format!("cdffacc612345678{}", "0".repeat(56))).unwrap(),
+                H256::from(facet).as_bytes().to_vec(),
+            );
+        }
+        // facets()
+        provider.set_call_result(
+            diamond.parse().unwrap(),
+            vec![0x7a, 0x0e, 0xd6, 0x27],
+            web3::ethabi::encode(&[Token::Array(vec![
+                Token::Tuple(vec![
+                    Token::Address(facet),
+                    Token::Array(vec![
+                        Token::FixedBytes(vec![0xa9, 0x05, 0x9c, 0xbb]),
+                        Token::FixedBytes(vec![0x09, 0x5e, 0xa7, 0xb3]),
+                        Token::FixedBytes(vec![0x70, 0xa0, 0x82, 0x31]),
+                    ]),
+                ]),
+                // a selector listed twice is routed once
+                Token::Tuple(vec![
+                    Token::Address(Address::from_low_u64_be(0xfac2)),
+                    Token::Array(vec![Token::FixedBytes(vec![0x09, 0x5e, 0xa7, 0xb3])]),
+                ]),
+            ])]),
+        );
+        let mut contract = Contract::from_source(&source_with(diamond, provider.clone()));
+
+        let mut delegate = external_call("", "", unresolved());
+        contract.resolve_diamond_facet(&mut delegate).await.unwrap();
+        assert_eq!(delegate.target_logic_addr, format!("{:?}", facet));
+        assert_eq!(delegate.resolution, CalleeResolution::DiamondFacet);
+        // a contract that is no loupe is not asked as one
+        let mut other = Contract::from_source(&source_with(not_diamond, provider));
+        let mut delegate = external_call("", "", unresolved());
+        other.resolve_diamond_facet(&mut delegate).await.unwrap();
+        assert!(delegate.target_logic_addr.is_empty());
+        assert_eq!(delegate.resolution, unresolved());
+        // a contract whose own dispatcher has the loupe functions is one
+        let mut dispatcher = Contract::from_source(&source_with(not_diamond, MockProvider::new()));
+        dispatcher
+            .func_sign_dict
+            .insert("0x5".to_string(), "0xcdffacc6".to_string());
+        assert!(dispatcher.is_diamond_loupe().await.unwrap());
+
+        // from the origin, the fallback delegates each selector of the loupe
+        // its dispatcher does not match itself
+        contract
+            .func_sign_dict
+            .insert("0x2".to_string(), "0x70a08231".to_string());
+        let mut fallback = external_call("", "", unresolved());
+        fallback.caller_func_sign = FALLBACK_FUNC_SIGN.to_string();
+        let calls = contract.diamond_facet_calls(&fallback).await.unwrap();
+        let edges: Vec<_> = calls
+            .iter()
+            .map(|call| {
+                (
+                    call.caller_func_sign.as_str(),
+                    call.target_func_sign.as_str(),
+                    call.target_logic_addr.clone(),
+                    call.target_storage_addr.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("0xa9059cbb", "0xa9059cbb", format!("{:?}", facet), ""),
+                ("0x095ea7b3", "0x095ea7b3", format!("{:?}", facet), ""),
+            ]
+        );
+        let mut entries: Vec<_> = contract.external_call_in_func_signature.iter().collect();
+        entries.sort();
+        assert_eq!(entries, vec!["0x095ea7b3", "0xa9059cbb"]);
+        // another function does not dispatch arbitrary selectors
+        let other = external_call("", "", unresolved());
+        assert!(contract
+            .diamond_facet_calls(&other)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -1,5 +1,8 @@
 use csv::StringRecord;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::contract::proxy::{MinimalProxy, ProxyLayout};
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub(crate) struct ConstantCallee {
@@ -78,6 +81,28 @@ impl From<StringRecord> for FuncArgCallee {
     }
 }
 
+/// Callee held in an element of a mapping or array, whose slot derives
+/// from the base slot of the data structure and a constant or argument key
+#[derive(Debug, Clone, Deserialize)]
+#[allow(dead_code)]
+pub(crate) struct StorageElementCallee {
+    pub(crate) func: String,
+    pub(crate) call_stmt: String,
+    /// `mapping` or `array`
+    pub(crate) kind: String,
+    pub(crate) base_slot: String,
+    /// `const` for a constant key, `arg` for an argument of the function
+    pub(crate) key_type: String,
+    /// Constant key, or index of the argument
+    pub(crate) key: String,
+    /// Words per array element
+    pub(crate) elem_size: u64,
+    /// Word offset of the struct member in the element
+    pub(crate) offset: u64,
+    pub(crate) byte_low: String,
+    pub(crate) byte_high: String,
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub(crate) struct ConstantFuncSign {
@@ -130,15 +155,72 @@ pub(crate) struct ExternalCallData {
     pub(crate) num_ret: String,
 }
 
-#[derive(Debug)]
+/// How the target address of an external call was recovered
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum CalleeResolution {
+    /// Hard-coded in the caller
+    Constant,
+    /// Read from a storage slot of the caller
+    Storage,
+    /// Read from an element of a mapping or array of the caller, keyed by
+    /// a constant or a known argument
+    StorageElement,
+    /// Read from the implementation slot of a proxy
+    ProxyStorage,
+    /// Written to a storage slot by the constructor, the slot being empty
+    /// at the snapshot block
+    ConstructorStorage,
+    /// Flows from an argument of the public function, for which the caller
+    /// passed no known value
+    FuncArg,
+    /// Flows from an argument of the public function, for which the caller
+    /// passed a known value
+    KnownArg,
+    /// Deployed by the caller through CREATE or CREATE2
+    Created,
+    /// Implementation behind a proxy following a standard storage layout
+    StandardProxy {
+        layout: ProxyLayout,
+    },
+    /// Implementation hard-coded in a minimal proxy
+    MinimalProxy {
+        kind: MinimalProxy,
+    },
+    /// Facet a diamond (EIP-2535) routes the called selector to
+    DiamondFacet,
+    Unresolved {
+        reason: String,
+    },
+}
+
+impl CalleeResolution {
+    /// Why a call with this resolution has no target address
+    pub(crate) fn missing_target_reason(&self) -> String {
+        match self {
+            CalleeResolution::Unresolved { reason } => reason.clone(),
+            CalleeResolution::FuncArg => {
+                "callee is a function argument without a known value".to_string()
+            }
+            _ => "callee resolved to an empty address".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 #[allow(dead_code)]
-pub(crate) struct ExternalCall {
+pub struct ExternalCall {
     pub(crate) target_logic_addr: String,
     pub(crate) target_storage_addr: String,
     pub(crate) target_func_sign: String,
     pub(crate) call_site: String,
     pub(crate) caller_func_sign: String,
     pub(crate) caller_addr: String,
+    pub(crate) caller_code_key: String,
+    /// Call statement in the code of the contract making the call
+    pub(crate) call_stmt: String,
+    pub(crate) call_op: String,
+    pub(crate) resolution: CalleeResolution,
 }
 
 #[derive(Debug)]
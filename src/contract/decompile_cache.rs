@@ -0,0 +1,258 @@
+//! Persistent decompilation cache keyed by the keccak hash of the bytecode
+//!
+//! The Leslie relations of every decompiled code are kept under the
+//! workspace's decompiled dir, so a code seen before (at another address,
+//! block, or in an earlier run) is never handed to Gigahorse again.
+//! Entries are evicted least recently used first once the cache outgrows
+//! its capacity.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use log::info;
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use crate::outputter::result_structure::CacheStats;
+use crate::workspace::{lock_dir, Workspace};
+
+const LAST_USED: &str = ".last_used";
+/// Entries used this recently are never evicted, since a concurrent run may
+/// still be reading them
+const MIN_IDLE: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Default)]
+pub struct DecompileCache {
+    capacity_bytes: u64,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+    evicted: AtomicUsize,
+    in_flight: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+}
+
+impl DecompileCache {
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            capacity_bytes,
+            ..Self::default()
+        }
+    }
+
+    /// Exclusive right to decompile `code_key` within this process. Held
+    /// around lookup and store, so concurrent analyses of the same code run
+    /// Gigahorse once and the others find the code cached.
+    pub async fn claim(&self, code_key: &str) -> OwnedMutexGuard<()> {
+        let slot = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(code_key.to_string())
+            .or_default()
+            .clone();
+        slot.lock_owned().await
+    }
+
+    /// Whether the relations of `code_key` are cached; a hit marks the entry
+    /// as recently used
+    pub fn lookup(&self, workspace: &Workspace, code_key: &str) -> bool {
+        let entry = workspace.decompiled_dir.join(code_key);
+        if workspace.decompiled_out_dir(code_key).is_dir() {
+            // a failed touch only makes the entry an earlier eviction candidate
+            let _ = touch(&entry);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            true
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    /// Keep the relations Gigahorse wrote for `code_key` in this run's
+    /// scratch dir. The entry is assembled aside and renamed into place, so
+    /// readers never see a partial entry.
+    pub fn store(&self, workspace: &Workspace, code_key: &str) -> io::Result<()> {
+        let target = workspace.decompiled_dir.join(code_key);
+        if target.exists() {
+            return Ok(());
+        }
+        let staging =
+            workspace
+                .decompiled_dir
+                .join(format!(".{}.{}.tmp", code_key, std::process::id()));
+        let staging_out = staging.join("out");
+        fs::create_dir_all(&staging_out)?;
+        for relation in fs::read_dir(workspace.scratch_out_dir(code_key))? {
+            let relation = relation?;
+            if relation.file_type()?.is_file() {
+                fs::copy(relation.path(), staging_out.join(relation.file_name()))?;
+            }
+        }
+        touch(&staging)?;
+        if let Err(e) = fs::rename(&staging, &target) {
+            fs::remove_dir_all(&staging)?;
+            // another run stored the same code first
+            if !target.exists() {
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove least recently used entries until the cache fits its capacity,
+    /// returning the number of removed entries
+    pub fn evict(&self, workspace: &Workspace) -> io::Result<usize> {
+        let _lock = lock_dir(&workspace.decompiled_dir, true)?;
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&workspace.decompiled_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with('.') || !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let path = entry.path();
+            entries.push((last_used(&path), dir_size(&path)?, path));
+        }
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort();
+
+        let now = SystemTime::now();
+        let mut evicted = 0;
+        for (used, size, path) in entries {
+            if total <= self.capacity_bytes {
+                break;
+            }
+            if now.duration_since(used).unwrap_or_default() < MIN_IDLE {
+                break;
+            }
+            fs::remove_dir_all(&path)?;
+            total -= size;
+            evicted += 1;
+        }
+        if evicted > 0 {
+            info!("evicted {} decompiled codes from the cache", evicted);
+        }
+        self.evicted.fetch_add(evicted, Ordering::Relaxed);
+        Ok(evicted)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evicted: self.evicted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn touch(entry: &Path) -> io::Result<()> {
+    File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(entry.join(LAST_USED))?
+        .set_modified(SystemTime::now())
+}
+
+fn last_used(entry: &Path) -> SystemTime {
+    fs::metadata(entry.join(LAST_USED))
+        .or_else(|_| fs::metadata(entry))
+        .and_then(|metadata| metadata.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += if metadata.is_dir() {
+            dir_size(&entry.path())?
+        } else {
+            metadata.len()
+        };
+    }
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace::TestWorkspace;
+
+    fn workspace(name: &str) -> TestWorkspace {
+        let test = TestWorkspace::new(name);
+        fs::create_dir_all(&test.workspace.decompiled_dir).unwrap();
+        test
+    }
+
+    fn decompile(workspace: &Workspace, code_key: &str) {
+        let out_dir = workspace.scratch_out_dir(code_key);
+        fs::create_dir_all(&out_dir).unwrap();
+        fs::write(
+            out_dir.join("Leslie_FunctionSelector.csv"),
+            "0x0\t0x12345678\n",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_store_then_hit() {
+        let test = workspace("decompile-hit");
+        let workspace = &test.workspace;
+        let cache = DecompileCache::new(u64::MAX);
+
+        assert!(!cache.lookup(workspace, "aa"));
+        decompile(workspace, "aa");
+        cache.store(workspace, "aa").unwrap();
+        cache.store(workspace, "aa").unwrap();
+        assert!(cache.lookup(workspace, "aa"));
+        assert_eq!(workspace.out_dir("aa"), workspace.decompiled_out_dir("aa"));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                evicted: 0
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_claim_is_per_code() {
+        let cache = DecompileCache::default();
+        let claim = cache.claim("aa").await;
+        // another code is not blocked, the same one is until released
+        drop(cache.claim("bb").await);
+        assert!(cache.in_flight.lock().unwrap()["aa"].try_lock().is_err());
+        drop(claim);
+        drop(cache.claim("aa").await);
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let test = workspace("decompile-evict");
+        let workspace = &test.workspace;
+        let cache = DecompileCache::new(0);
+        for code_key in ["old", "recent"] {
+            decompile(workspace, code_key);
+            cache.store(workspace, code_key).unwrap();
+        }
+        let long_ago = SystemTime::now() - 2 * MIN_IDLE;
+        File::options()
+            .write(true)
+            .open(workspace.decompiled_dir.join("old").join(LAST_USED))
+            .unwrap()
+            .set_modified(long_ago)
+            .unwrap();
+
+        // the recently used entry survives although the cache is over capacity
+        assert_eq!(cache.evict(workspace).unwrap(), 1);
+        assert!(!workspace.decompiled_dir.join("old").exists());
+        assert!(workspace.decompiled_dir.join("recent").exists());
+        assert_eq!(cache.stats().evicted, 1);
+    }
+}
@@ -0,0 +1,139 @@
+//! Deployment transactions of contracts, used to recover creation code
+//!
+//! Attacker contracts often run the whole attack in their constructor or
+//! self-destruct afterwards, leaving no runtime code at any block. Their
+//! creation code is the input of their deployment transaction, found
+//! through an index mapping addresses to deployment transaction hashes:
+//! `{"<addr>": "<tx hash>"}`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use log::{info, warn};
+use web3::types::{Address, H256};
+
+use crate::config::ConfigError;
+use crate::error::LydiaResult;
+use crate::provider::StateProvider;
+
+#[derive(Debug, Default, Clone)]
+pub struct DeploymentIndex {
+    txs: HashMap<Address, H256>,
+}
+
+impl DeploymentIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            ConfigError::ParseError(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        Self::from_json_str(&content)
+    }
+
+    pub fn from_json_str(content: &str) -> Result<Self, ConfigError> {
+        let raw: HashMap<String, String> = serde_json::from_str(content)
+            .map_err(|e| ConfigError::ParseError(format!("deployment index: {}", e)))?;
+        let mut index = Self::new();
+        for (addr, hash) in raw {
+            let addr = Address::from_str(&addr).map_err(|e| {
+                ConfigError::ParseError(format!("deployment index: address {}: {}", addr, e))
+            })?;
+            let hash = H256::from_str(&hash).map_err(|e| {
+                ConfigError::ParseError(format!("deployment index: tx hash {}: {}", hash, e))
+            })?;
+            index.insert(addr, hash);
+        }
+        Ok(index)
+    }
+
+    pub fn insert(&mut self, addr: Address, tx_hash: H256) {
+        self.txs.insert(addr, tx_hash);
+    }
+
+    pub fn get(&self, addr: &Address) -> Option<H256> {
+        self.txs.get(addr).copied()
+    }
+
+    /// Creation code of `addr`, taken from its deployment transaction. Only
+    /// a transaction creating the contract itself carries its creation code;
+    /// contracts deployed by a factory are left to the caller.
+    pub async fn creation_code(
+        &self,
+        provider: &dyn StateProvider,
+        addr: &Address,
+    ) -> LydiaResult<Option<Vec<u8>>> {
+        let hash = match self.get(addr) {
+            Some(hash) => hash,
+            None => return Ok(None),
+        };
+        let transaction = match provider.get_transaction(hash).await? {
+            Some(transaction) => transaction,
+            None => {
+                warn!("deployment transaction {:?} of {:?} not found", hash, addr);
+                return Ok(None);
+            }
+        };
+        if transaction.to.is_some() {
+            warn!(
+                "{:?} is not deployed by a creation transaction ({:?} calls {:?})",
+                addr, hash, transaction.to
+            );
+            return Ok(None);
+        }
+        info!("creation code of {:?} taken from {:?}", addr, hash);
+        Ok(Some(transaction.input.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+    use web3::types::{Bytes, Transaction};
+
+    #[tokio::test]
+    async fn test_creation_code_from_deployment() {
+        let index = DeploymentIndex::from_json_str(
+            r#"{
+                "0x000000000000000000000000000000000000c0de": "0x00000000000000000000000000000000000000000000000000000000000000aa",
+                "0x000000000000000000000000000000000000beef": "0x00000000000000000000000000000000000000000000000000000000000000bb"
+            }"#,
+        )
+        .unwrap();
+        let mut provider = MockProvider::new();
+        provider.add_transaction(Transaction {
+            hash: H256::from_low_u64_be(0xaa),
+            input: Bytes(vec![0x60, 0x80]),
+            ..Transaction::default()
+        });
+        // deployed through a factory, whose input is not the creation code
+        provider.add_transaction(Transaction {
+            hash: H256::from_low_u64_be(0xbb),
+            to: Some(Address::from_low_u64_be(0xfac)),
+            input: Bytes(vec![0x12]),
+            ..Transaction::default()
+        });
+
+        let created = Address::from_low_u64_be(0xc0de);
+        assert_eq!(
+            index.creation_code(&provider, &created).await.unwrap(),
+            Some(vec![0x60, 0x80])
+        );
+        let by_factory = Address::from_low_u64_be(0xbeef);
+        assert_eq!(
+            index.creation_code(&provider, &by_factory).await.unwrap(),
+            None
+        );
+        let unknown = Address::from_low_u64_be(0x1);
+        assert_eq!(
+            index.creation_code(&provider, &unknown).await.unwrap(),
+            None
+        );
+        assert!(DeploymentIndex::from_json_str(r#"{"0x1": "0xaa"}"#).is_err());
+    }
+}
@@ -0,0 +1,255 @@
+//! Diamond (EIP-2535) facet lookup
+//!
+//! A diamond routes each function selector to its own facet, which its
+//! fallback delegates to. A contract is only asked as a loupe once it is
+//! known to be one: its dispatcher has the loupe functions, or it declares
+//! the `IDiamondLoupe` interface through ERC-165. The facet of a selector is
+//! then asked from the loupe (`facetAddress(bytes4)`); otherwise it is read
+//! from the selector mapping of the reference implementation, which only a
+//! diamond has set.
+
+use log::debug;
+use web3::ethabi::{self, ParamType, Token};
+use web3::signing::keccak256;
+use web3::types::{Address, BlockNumber, H256, U256};
+
+use crate::contract::status_fetcher::StorageWord;
+use crate::error::{LydiaError, LydiaResult};
+use crate::provider::StateProvider;
+
+/// `facetAddress(bytes4)`
+const FACET_ADDRESS: [u8; 4] = [0xcd, 0xff, 0xac, 0xc6];
+/// `facets()`
+const FACETS: [u8; 4] = [0x7a, 0x0e, 0xd6, 0x27];
+/// `facetFunctionSelectors(address)`
+const FACET_FUNCTION_SELECTORS: [u8; 4] = [0xad, 0xfc, 0xa1, 0x5e];
+/// `facetAddresses()`
+const FACET_ADDRESSES: [u8; 4] = [0x52, 0xef, 0x6b, 0x2c];
+/// `supportsInterface(bytes4)` of ERC-165
+const SUPPORTS_INTERFACE: [u8; 4] = [0x01, 0xff, 0xc9, 0xa7];
+/// ERC-165 identifier of `IDiamondLoupe`
+const LOUPE_INTERFACE: [u8; 4] = [0x48, 0xe2, 0xb0, 0x93];
+/// Slot of the diamond storage: `keccak256("diamond.standard.diamond.storage")`
+const DIAMOND_STORAGE_LABEL: &str = "diamond.standard.diamond.storage";
+
+/// Parse a function signature such as `0xa9059cbb`
+pub fn parse_selector(func_sign: &str) -> Option<[u8; 4]> {
+    let bytes = hex::decode(func_sign.trim_start_matches("0x")).ok()?;
+    bytes.try_into().ok()
+}
+
+/// Whether `func_sign` is a function of the loupe
+pub fn is_loupe_function(func_sign: &str) -> bool {
+    parse_selector(func_sign).is_some_and(|selector| {
+        [
+            FACET_ADDRESS,
+            FACETS,
+            FACET_FUNCTION_SELECTORS,
+            FACET_ADDRESSES,
+        ]
+        .contains(&selector)
+    })
+}
+
+/// Whether `contract` declares the loupe interface through ERC-165. A
+/// contract without ERC-165 reverts, which only tells it is no loupe.
+pub async fn supports_loupe(
+    provider: &dyn StateProvider,
+    contract: Address,
+    block: Option<BlockNumber>,
+) -> bool {
+    let mut data = SUPPORTS_INTERFACE.to_vec();
+    data.extend_from_slice(&LOUPE_INTERFACE);
+    data.resize(4 + 32, 0);
+    match provider.call(contract, data, block).await {
+        Ok(output) => output
+            .get(..32)
+            .is_some_and(|word| U256::from_big_endian(word) == U256::one()),
+        Err(e) => {
+            debug!("supportsInterface of {:?} failed: {}", contract, e);
+            false
+        }
+    }
+}
+
+/// Facet the loupe `diamond` routes `selector` to, if any
+pub async fn facet_address(
+    provider: &dyn StateProvider,
+    diamond: Address,
+    selector: [u8; 4],
+    block: Option<BlockNumber>,
+) -> LydiaResult<Option<Address>> {
+    let mut data = FACET_ADDRESS.to_vec();
+    data.extend_from_slice(&selector);
+    data.resize(4 + 32, 0);
+    match provider.call(diamond, data, block).await {
+        Ok(output) if output.len() >= 32 => {
+            return Ok(StorageWord::from(H256::from_slice(&output[..32])).nonzero_address());
+        }
+        Ok(_) => {}
+        // reverting: fall back to the selector mapping
+        Err(e) => debug!("facetAddress of {:?} failed: {}", diamond, e),
+    }
+    mapped_facet(provider, diamond, selector, block).await
+}
+
+/// Facet the selector mapping of the reference diamond storage routes
+/// `selector` to, if any
+pub async fn mapped_facet(
+    provider: &dyn StateProvider,
+    diamond: Address,
+    selector: [u8; 4],
+    block: Option<BlockNumber>,
+) -> LydiaResult<Option<Address>> {
+    match provider
+        .get_storage(diamond, selector_slot(selector), block)
+        .await
+    {
+        // `FacetAddressAndPosition` packs the facet into the low bytes
+        Ok(word) => Ok(StorageWord::from(word).nonzero_address()),
+        Err(LydiaError::StateUnavailable(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Selectors of every facet of the loupe `diamond`, empty when it does not
+/// answer
+pub async fn facets(
+    provider: &dyn StateProvider,
+    diamond: Address,
+    block: Option<BlockNumber>,
+) -> LydiaResult<Vec<(Address, Vec<[u8; 4]>)>> {
+    let output = match provider.call(diamond, FACETS.to_vec(), block).await {
+        Ok(output) => output,
+        Err(e) => {
+            debug!("facets of {:?} failed: {}", diamond, e);
+            return Ok(Vec::new());
+        }
+    };
+    let facet = ParamType::Tuple(vec![
+        ParamType::Address,
+        ParamType::Array(Box::new(ParamType::FixedBytes(4))),
+    ]);
+    let tokens = match ethabi::decode(&[ParamType::Array(Box::new(facet))], &output) {
+        Ok(tokens) => tokens,
+        Err(e) => {
+            debug!("facets of {:?} are not ABI encoded: {}", diamond, e);
+            return Ok(Vec::new());
+        }
+    };
+    let mut facets = Vec::new();
+    for token in tokens.into_iter().filter_map(Token::into_array).flatten() {
+        if let Some([Token::Address(address), Token::Array(selectors)]) =
+            token.into_tuple().as_deref()
+        {
+            let selectors = selectors
+                .iter()
+                .filter_map(|selector| selector.clone().into_fixed_bytes()?.try_into().ok())
+                .collect();
+            facets.push((*address, selectors));
+        }
+    }
+    Ok(facets)
+}
+
+/// Slot of `selectorToFacetAndPosition[selector]`, the first member of the
+/// diamond storage in the reference implementation
+fn selector_slot(selector: [u8; 4]) -> U256 {
+    // a bytes4 key is left aligned in its word
+    let mut preimage = [0u8; 64];
+    preimage[..4].copy_from_slice(&selector);
+    preimage[32..].copy_from_slice(&keccak256(DIAMOND_STORAGE_LABEL.as_bytes()));
+    U256::from_big_endian(&keccak256(&preimage))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+
+    #[tokio::test]
+    async fn test_facet_lookup() {
+        let transfer = parse_selector("0xa9059cbb").unwrap();
+        let approve = parse_selector("0x095ea7b3").unwrap();
+        let token_facet = Address::from_low_u64_be(0xfac1);
+        let admin_facet = Address::from_low_u64_be(0xfac2);
+
+        // a diamond with a loupe
+        let diamond = Address::from_low_u64_be(0xd1);
+        let mut provider = MockProvider::new();
+        let mut query = FACET_ADDRESS.to_vec();
+        query.extend_from_slice(&transfer);
+        query.resize(36, 0);
+        provider.set_call_result(diamond, query, H256::from(token_facet).as_bytes().to_vec());
+        provider.set_call_result(
+            diamond,
+            FACETS.to_vec(),
+            ethabi::encode(&[Token::Array(vec![
+                Token::Tuple(vec![
+                    Token::Address(token_facet),
+                    Token::Array(vec![
+                        Token::FixedBytes(transfer.to_vec()),
+                        Token::FixedBytes(approve.to_vec()),
+                    ]),
+                ]),
+                Token::Tuple(vec![Token::Address(admin_facet), Token::Array(vec![])]),
+            ])]),
+        );
+        // a diamond without loupe, with the reference storage layout
+        let bare = Address::from_low_u64_be(0xd2);
+        let mut word = [0u8; 32];
+        word[12..].copy_from_slice(admin_facet.as_bytes());
+        // the selector position shares the slot
+        word[11] = 0x01;
+        provider.set_storage(bare, selector_slot(approve), H256::from(word));
+
+        assert_eq!(
+            facet_address(&provider, diamond, transfer, None)
+                .await
+                .unwrap(),
+            Some(token_facet)
+        );
+        assert_eq!(
+            facet_address(&provider, bare, approve, None).await.unwrap(),
+            Some(admin_facet)
+        );
+        assert_eq!(
+            facet_address(&provider, bare, transfer, None)
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            facets(&provider, diamond, None).await.unwrap(),
+            vec![
+                (token_facet, vec![transfer, approve]),
+                (admin_facet, vec![])
+            ]
+        );
+        assert!(facets(&provider, bare, None).await.unwrap().is_empty());
+        assert_eq!(parse_selector("0x12"), None);
+        assert!(is_loupe_function("0xcdffacc6"));
+        assert!(!is_loupe_function("0xa9059cbb"));
+    }
+
+    #[tokio::test]
+    async fn test_loupe_interface() {
+        let diamond = Address::from_low_u64_be(0xd1);
+        let other = Address::from_low_u64_be(0xd2);
+        let mut query = SUPPORTS_INTERFACE.to_vec();
+        query.extend_from_slice(&LOUPE_INTERFACE);
+        query.resize(36, 0);
+        let mut provider = MockProvider::new();
+        provider.set_call_result(
+            diamond,
+            query.clone(),
+            H256::from_low_u64_be(1).as_bytes().to_vec(),
+        );
+        provider.set_call_result(other, query, H256::zero().as_bytes().to_vec());
+
+        assert!(supports_loupe(&provider, diamond, None).await);
+        assert!(!supports_loupe(&provider, other, None).await);
+        // no ERC-165 at all
+        assert!(!supports_loupe(&provider, Address::from_low_u64_be(0xd3), None).await);
+    }
+}
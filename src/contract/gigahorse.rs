@@ -0,0 +1,215 @@
+//! Invocation of the Gigahorse decompiler and its Leslie client
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use serde_json::Value;
+use tokio::process::Command;
+
+use crate::error::{LydiaError, LydiaResult};
+use crate::workspace::Workspace;
+
+const ENTRY_POINT: &str = "gigahorse.py";
+/// Time allowed on top of the decompiler timeout before the whole toolchain
+/// process is killed, covering Souffle compiling the client on first use
+const PROCESS_GRACE: Duration = Duration::from_secs(300);
+
+/// Runs Gigahorse with a Datalog client on one bytecode file at a time
+#[derive(Debug, Clone)]
+pub struct GigahorseRunner {
+    client: PathBuf,
+    timeout: Duration,
+}
+
+impl GigahorseRunner {
+    /// `client` is resolved relative to the toolchain directory; `timeout`
+    /// bounds the decompilation and client analysis of each contract
+    pub fn new(client: PathBuf, timeout: Duration) -> Self {
+        Self { client, timeout }
+    }
+
+    /// Decompile `bytecode` and run the client on it, writing the output
+    /// relations into the workspace's temp dir. Fails on timeouts, Souffle
+    /// errors and a missing toolchain.
+    pub async fn run(&self, workspace: &Workspace, bytecode: &Path) -> LydiaResult<()> {
+        let toolchain_dir = &workspace.toolchain_dir;
+        let entry_point = toolchain_dir
+            .join(ENTRY_POINT)
+            .canonicalize()
+            .map_err(|e| {
+                LydiaError::ToolchainMissing(format!(
+                    "{}: {}",
+                    toolchain_dir.join(ENTRY_POINT).display(),
+                    e
+                ))
+            })?;
+        if !toolchain_dir.join(&self.client).is_file() {
+            return Err(LydiaError::ToolchainMissing(format!(
+                "client {} not found",
+                toolchain_dir.join(&self.client).display()
+            )));
+        }
+        let bytecode = bytecode.canonicalize()?;
+        let contract_name = bytecode
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let results_file = std::path::absolute(workspace.results_path(&contract_name))?;
+        let code_key = bytecode
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let private_cache = std::path::absolute(workspace.private_cache_dir(&code_key))?;
+        with_cache(workspace, &private_cache, Workspace::seed_private_cache).await?;
+
+        let start = Instant::now();
+        let mut command = Command::new(&entry_point);
+        command
+            .current_dir(toolchain_dir)
+            .arg("-C")
+            .arg(&self.client)
+            .arg("-T")
+            .arg(self.timeout.as_secs().to_string())
+            .arg("-w")
+            .arg(std::path::absolute(&workspace.scratch_dir)?)
+            .arg("--cache_dir")
+            .arg(&private_cache)
+            .arg("-r")
+            .arg(&results_file)
+            .arg(&bytecode)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        let output = tokio::time::timeout(self.timeout + PROCESS_GRACE, command.output()).await;
+        // share whatever got compiled, even when the analysis itself failed
+        with_cache(workspace, &private_cache, Workspace::publish_private_cache).await?;
+        let output = match output {
+            Ok(output) => output.map_err(|e| {
+                LydiaError::ToolchainMissing(format!("cannot run {}: {}", entry_point.display(), e))
+            })?,
+            Err(_) => {
+                return Err(LydiaError::DecompilerTimeout(format!(
+                    "{} killed after {:?}",
+                    contract_name,
+                    start.elapsed()
+                )))
+            }
+        };
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            debug!("gigahorse: {}", line);
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        for line in stderr.lines() {
+            warn!("gigahorse: {}", line);
+        }
+        info!(
+            "gigahorse on {} consumes {:?}",
+            contract_name,
+            start.elapsed()
+        );
+
+        if !output.status.success() {
+            return Err(LydiaError::SouffleCrash(format!(
+                "{} exited with {}: {}",
+                contract_name,
+                output.status,
+                last_line(&stderr)
+            )));
+        }
+        let results = tokio::fs::read_to_string(&results_file).await?;
+        check_results(&results, &contract_name)
+    }
+}
+
+/// Run a blocking operation on the shared client cache off the runtime
+async fn with_cache(
+    workspace: &Workspace,
+    private_cache: &Path,
+    operation: fn(&Workspace, &Path) -> std::io::Result<()>,
+) -> LydiaResult<()> {
+    let workspace = workspace.clone();
+    let private_cache = private_cache.to_path_buf();
+    tokio::task::spawn_blocking(move || operation(&workspace, &private_cache))
+        .await
+        .map_err(|e| LydiaError::General(e.to_string()))??;
+    Ok(())
+}
+
+fn last_line(text: &str) -> &str {
+    text.lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("")
+}
+
+/// Map the meta flags Gigahorse reports for `contract_name` in its results
+/// file (`[[name, files, meta, analytics], ..]`) to errors
+fn check_results(results: &str, contract_name: &str) -> LydiaResult<()> {
+    let results: Vec<Value> = serde_json::from_str(results)?;
+    let meta = results
+        .iter()
+        .find(|entry| entry.get(0).and_then(Value::as_str) == Some(contract_name))
+        .and_then(|entry| entry.get(2))
+        .and_then(Value::as_array)
+        .ok_or_else(|| {
+            LydiaError::SouffleCrash(format!("{} missing from Gigahorse results", contract_name))
+        })?;
+    for flag in meta.iter().filter_map(Value::as_str) {
+        match flag {
+            "TIMEOUT" | "CLIENT TIMEOUT" => {
+                return Err(LydiaError::DecompilerTimeout(format!(
+                    "{}: {}",
+                    contract_name, flag
+                )))
+            }
+            "ERROR" | "CLIENT ERROR" => {
+                return Err(LydiaError::SouffleCrash(format!(
+                    "{}: {}",
+                    contract_name, flag
+                )))
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_results() {
+        let ok = r#"[["a.hex", ["Leslie_FunctionSelector"], [], {}]]"#;
+        assert!(check_results(ok, "a.hex").is_ok());
+
+        let timeout = r#"[["b.hex", [], ["TIMEOUT"], {}], ["a.hex", [], [], {}]]"#;
+        assert!(check_results(timeout, "a.hex").is_ok());
+        assert!(matches!(
+            check_results(timeout, "b.hex"),
+            Err(LydiaError::DecompilerTimeout(_))
+        ));
+
+        let crash = r#"[["a.hex", [], ["CLIENT ERROR"], {}]]"#;
+        assert!(matches!(
+            check_results(crash, "a.hex"),
+            Err(LydiaError::SouffleCrash(_))
+        ));
+        assert!(matches!(
+            check_results("[]", "a.hex"),
+            Err(LydiaError::SouffleCrash(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_missing_toolchain() {
+        let runner =
+            GigahorseRunner::new(PathBuf::from("./clients/leslie.dl"), Duration::from_secs(1));
+        let workspace = Workspace::new(PathBuf::from("./no-such-toolchain"));
+        assert!(matches!(
+            runner.run(&workspace, Path::new("./missing.hex")).await,
+            Err(LydiaError::ToolchainMissing(_))
+        ));
+    }
+}
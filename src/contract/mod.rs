@@ -1,3 +1,10 @@
+pub mod bytecode;
+#[allow(clippy::module_inception)]
 pub mod contract;
 pub mod data_structure;
+pub mod decompile_cache;
+pub mod deployment;
+pub mod diamond;
+pub mod gigahorse;
+pub mod proxy;
 pub mod status_fetcher;
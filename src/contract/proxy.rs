@@ -0,0 +1,306 @@
+//! Standard proxy layouts
+//!
+//! Upgradeable proxies keep the address of their logic contract (or of a
+//! beacon pointing to it) in a storage slot fixed by a standard, so the
+//! implementation behind a proxy can be read without knowing how its code
+//! loads it. Minimal proxies (clones) hard-code it in a fixed bytecode.
+
+use serde::{Deserialize, Serialize};
+use web3::types::{Address, BlockNumber, H256, U256};
+
+use crate::contract::status_fetcher::StorageWord;
+use crate::error::{LydiaError, LydiaResult};
+use crate::provider::StateProvider;
+
+const PUSH20: u8 = 0x73;
+
+/// `implementation()` of a beacon
+const BEACON_IMPLEMENTATION: [u8; 4] = [0x5c, 0x60, 0xda, 0x1b];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ProxyLayout {
+    /// EIP-1967 implementation slot
+    Eip1967,
+    /// EIP-1967 beacon slot, the beacon returning the implementation
+    Eip1967Beacon,
+    /// EIP-1822 (UUPS) `PROXIABLE` slot
+    Eip1822,
+    /// Implementation slot of the OpenZeppelin (zos) proxies before EIP-1967
+    OpenZeppelinLegacy,
+}
+
+/// Minimal proxy runtimes delegating every call to a hard-coded implementation
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum MinimalProxy {
+    /// EIP-1167 clone
+    Eip1167,
+    /// 0age's more minimal proxy
+    ZeroAge,
+    /// Solady clone using PUSH0
+    SoladyPush0,
+}
+
+impl MinimalProxy {
+    const ALL: [MinimalProxy; 3] = [
+        MinimalProxy::Eip1167,
+        MinimalProxy::ZeroAge,
+        MinimalProxy::SoladyPush0,
+    ];
+
+    /// Code before and after the `PUSH20 <implementation>`
+    fn pattern(&self) -> (&'static [u8], &'static [u8]) {
+        match self {
+            MinimalProxy::Eip1167 => (
+                &[0x36, 0x3d, 0x3d, 0x37, 0x3d, 0x3d, 0x3d, 0x36, 0x3d],
+                &[
+                    0x5a, 0xf4, 0x3d, 0x82, 0x80, 0x3e, 0x90, 0x3d, 0x91, 0x60, 0x2b, 0x57, 0xfd,
+                    0x5b, 0xf3,
+                ],
+            ),
+            MinimalProxy::ZeroAge => (
+                &[0x3d, 0x3d, 0x3d, 0x3d, 0x36, 0x3d, 0x3d, 0x37, 0x36, 0x3d],
+                &[
+                    0x5a, 0xf4, 0x3d, 0x3d, 0x93, 0x80, 0x3e, 0x60, 0x2a, 0x57, 0xfd, 0x5b, 0xf3,
+                ],
+            ),
+            MinimalProxy::SoladyPush0 => (
+                &[0x5f, 0x5f, 0x36, 0x5f, 0x5f, 0x37, 0x36, 0x5f],
+                &[
+                    0x5a, 0xf4, 0x3d, 0x5f, 0x5f, 0x3e, 0x60, 0x29, 0x57, 0x3d, 0x5f, 0xfd, 0x5b,
+                    0x3d, 0x5f, 0xf3,
+                ],
+            ),
+        }
+    }
+
+    /// Kind of minimal proxy `code` is, with its implementation and the
+    /// offset of its DELEGATECALL
+    pub fn detect(code: &[u8]) -> Option<(MinimalProxy, Address, usize)> {
+        Self::ALL.into_iter().find_map(|kind| {
+            let (prefix, suffix) = kind.pattern();
+            let rest = code.strip_prefix(prefix)?.strip_prefix(&[PUSH20])?;
+            if rest.len() != 20 + suffix.len() || !rest.ends_with(suffix) {
+                return None;
+            }
+            // GAS follows the implementation, then DELEGATECALL
+            let delegatecall = prefix.len() + 1 + 20 + 1;
+            Some((kind, Address::from_slice(&rest[..20]), delegatecall))
+        })
+    }
+}
+
+/// Contracts behind a proxy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyTarget {
+    pub implementation: Address,
+    pub beacon: Option<Address>,
+}
+
+impl ProxyLayout {
+    /// Layouts in the order a proxy is probed for them
+    pub const ALL: [ProxyLayout; 4] = [
+        ProxyLayout::Eip1967,
+        ProxyLayout::Eip1967Beacon,
+        ProxyLayout::Eip1822,
+        ProxyLayout::OpenZeppelinLegacy,
+    ];
+
+    /// Storage slot holding the implementation, or the beacon
+    pub fn slot(&self) -> U256 {
+        let slot = match self {
+            // keccak256("eip1967.proxy.implementation") - 1
+            ProxyLayout::Eip1967 => {
+                "360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc"
+            }
+            // keccak256("eip1967.proxy.beacon") - 1
+            ProxyLayout::Eip1967Beacon => {
+                "a3f0ad74e5423aebfd80d3ef4346578335a9a72aeaee59ff6cb3582b35133d50"
+            }
+            // keccak256("PROXIABLE")
+            ProxyLayout::Eip1822 => {
+                "c5f16f0fcc639fa48a6947836d9850f504798523bf8c9a3a87d5876cf622bcf7"
+            }
+            // keccak256("org.zeppelinos.proxy.implementation")
+            ProxyLayout::OpenZeppelinLegacy => {
+                "7050c9e0f4ca769c69bd3a8ef740bc37934f8e2c036e5a723fd8ee048ed3f8c3"
+            }
+        };
+        U256::from_str_radix(slot, 16).expect("valid proxy slot")
+    }
+
+    /// Layout whose slot is `slot`, e.g. a slot Leslie found a proxy to load
+    /// its delegate from
+    pub fn from_slot(slot: &U256) -> Option<Self> {
+        Self::ALL.into_iter().find(|layout| layout.slot() == *slot)
+    }
+
+    /// Contracts behind `proxy` in this layout, `None` when the slot is
+    /// empty or unknown to the provider
+    pub async fn resolve(
+        &self,
+        provider: &dyn StateProvider,
+        proxy: Address,
+        block: Option<BlockNumber>,
+    ) -> LydiaResult<Option<ProxyTarget>> {
+        let word = match provider.get_storage(proxy, self.slot(), block).await {
+            Ok(word) => word,
+            Err(LydiaError::StateUnavailable(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let pointer = match StorageWord::from(word).nonzero_address() {
+            Some(pointer) => pointer,
+            None => return Ok(None),
+        };
+        if *self != ProxyLayout::Eip1967Beacon {
+            return Ok(Some(ProxyTarget {
+                implementation: pointer,
+                beacon: None,
+            }));
+        }
+        let output = match provider
+            .call(pointer, BEACON_IMPLEMENTATION.to_vec(), block)
+            .await
+        {
+            Ok(output) => output,
+            Err(LydiaError::StateUnavailable(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(output
+            .get(..32)
+            .and_then(|word| StorageWord::from(H256::from_slice(word)).nonzero_address())
+            .map(|implementation| ProxyTarget {
+                implementation,
+                beacon: Some(pointer),
+            }))
+    }
+
+    /// The first of `layouts` `proxy` follows, with the contracts behind it
+    pub async fn detect(
+        layouts: &[ProxyLayout],
+        provider: &dyn StateProvider,
+        proxy: Address,
+        block: Option<BlockNumber>,
+    ) -> LydiaResult<Option<(ProxyLayout, ProxyTarget)>> {
+        for layout in layouts {
+            if let Some(target) = layout.resolve(provider, proxy, block).await? {
+                return Ok(Some((*layout, target)));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::bytecode::decode_hex;
+    use crate::provider::MockProvider;
+    use web3::signing::keccak256;
+
+    #[test]
+    fn test_standard_slots() {
+        let hashed = |label: &str| U256::from_big_endian(&keccak256(label.as_bytes()));
+        assert_eq!(
+            ProxyLayout::Eip1967.slot(),
+            hashed("eip1967.proxy.implementation") - 1
+        );
+        assert_eq!(
+            ProxyLayout::Eip1967Beacon.slot(),
+            hashed("eip1967.proxy.beacon") - 1
+        );
+        assert_eq!(ProxyLayout::Eip1822.slot(), hashed("PROXIABLE"));
+        assert_eq!(
+            ProxyLayout::OpenZeppelinLegacy.slot(),
+            hashed("org.zeppelinos.proxy.implementation")
+        );
+        assert_eq!(
+            ProxyLayout::from_slot(&ProxyLayout::Eip1822.slot()),
+            Some(ProxyLayout::Eip1822)
+        );
+        assert_eq!(ProxyLayout::from_slot(&U256::zero()), None);
+    }
+
+    #[test]
+    fn test_detect_minimal_proxy() {
+        let implementation = "bebebebebebebebebebebebebebebebebebebebe";
+        let clones = [
+            (
+                MinimalProxy::Eip1167,
+                format!(
+                    "363d3d373d3d3d363d73{}5af43d82803e903d91602b57fd5bf3",
+                    implementation
+                ),
+            ),
+            (
+                MinimalProxy::ZeroAge,
+                format!(
+                    "3d3d3d3d363d3d37363d73{}5af43d3d93803e602a57fd5bf3",
+                    implementation
+                ),
+            ),
+            (
+                MinimalProxy::SoladyPush0,
+                format!(
+                    "5f5f365f5f37365f73{}5af43d5f5f3e6029573d5ffd5b3d5ff3",
+                    implementation
+                ),
+            ),
+        ];
+        for (kind, code) in clones {
+            let code = decode_hex(&code).unwrap();
+            let (detected, address, delegatecall) = MinimalProxy::detect(&code).unwrap();
+            assert_eq!(detected, kind);
+            assert_eq!(address, implementation.parse().unwrap());
+            assert_eq!(code[delegatecall], 0xf4);
+        }
+        // code appended to a clone makes it a different contract
+        let extended = decode_hex(&format!(
+            "363d3d373d3d3d363d73{}5af43d82803e903d91602b57fd5bf300",
+            implementation
+        ))
+        .unwrap();
+        assert_eq!(MinimalProxy::detect(&extended), None);
+    }
+
+    #[tokio::test]
+    async fn test_detect_beacon_proxy() {
+        let proxy = Address::from_low_u64_be(0x1);
+        let beacon = Address::from_low_u64_be(0xbeac);
+        let implementation = Address::from_low_u64_be(0x10c1c);
+        let mut provider = MockProvider::new();
+        provider.set_storage(proxy, ProxyLayout::Eip1967.slot(), H256::zero());
+        provider.set_storage(proxy, ProxyLayout::Eip1967Beacon.slot(), H256::from(beacon));
+        provider.set_call_result(
+            beacon,
+            BEACON_IMPLEMENTATION.to_vec(),
+            H256::from(implementation).as_bytes().to_vec(),
+        );
+
+        assert_eq!(
+            ProxyLayout::detect(&ProxyLayout::ALL, &provider, proxy, None)
+                .await
+                .unwrap(),
+            Some((
+                ProxyLayout::Eip1967Beacon,
+                ProxyTarget {
+                    implementation,
+                    beacon: Some(beacon),
+                }
+            ))
+        );
+        // the slots of other layouts are empty or unknown
+        assert_eq!(
+            ProxyLayout::detect(
+                &[ProxyLayout::Eip1967, ProxyLayout::Eip1822],
+                &provider,
+                proxy,
+                None
+            )
+            .await
+            .unwrap(),
+            None
+        );
+    }
+}
@@ -1,10 +1,13 @@
-use log::info;
 use std::error::Error;
+use std::fmt;
 use std::str::FromStr;
-use std::time::Instant;
 use web3::{
+    signing::keccak256,
     transports::{Http, WebSocket},
-    types::{H160, U256},
+    types::{
+        Address, BlockId, BlockNumber, Bytes, CallRequest, Transaction, TransactionId,
+        TransactionReceipt, H160, H256, U256,
+    },
     Web3,
 };
 
@@ -15,7 +18,7 @@ pub(crate) enum Web3Transport {
 
 impl Web3Transport {
     pub async fn new(url: &str) -> Result<Self, Box<dyn Error>> {
-        if url.starts_with("https") {
+        if url.starts_with("http") {
             let http = Http::new(url)?;
             Ok(Web3Transport::Http(Web3::new(http)))
         } else {
@@ -29,51 +32,342 @@ impl Web3Transport {
     pub async fn get_code(
         &self,
         address: web3::types::Address,
+        block: Option<BlockNumber>,
     ) -> web3::Result<web3::types::Bytes> {
         match self {
-            Web3Transport::Http(web3) => web3.eth().code(address, None).await,
-            Web3Transport::WebSocket(web3) => web3.eth().code(address, None).await,
+            Web3Transport::Http(web3) => web3.eth().code(address, block).await,
+            Web3Transport::WebSocket(web3) => web3.eth().code(address, block).await,
         }
     }
 
     pub async fn get_storage(
         &self,
         storage_addr: H160,
-        slot_index: &str,
-        byte_low: &str,
-        byte_high: &str,
-    ) -> Result<String, Box<dyn Error>> {
-        let start = Instant::now();
-        let slot_index =
-            U256::from_str(slot_index).map_err(|e| format!("Failed to parse slot_index: {}", e))?;
-
-        // Parse byte_low and byte_high from string to usize
-        let byte_low =
-            usize::from_str(byte_low).map_err(|e| format!("Failed to parse byte_low: {}", e))?;
-        let byte_high =
-            usize::from_str(byte_high).map_err(|e| format!("Failed to parse byte_high: {}", e))?;
-        let storage_content = match self {
-            Web3Transport::Http(web3) => web3.eth().storage(storage_addr, slot_index, None).await?,
+        slot_index: U256,
+        block: Option<BlockNumber>,
+    ) -> web3::Result<H256> {
+        match self {
+            Web3Transport::Http(web3) => web3.eth().storage(storage_addr, slot_index, block).await,
             Web3Transport::WebSocket(web3) => {
-                web3.eth().storage(storage_addr, slot_index, None).await?
+                web3.eth().storage(storage_addr, slot_index, block).await
             }
+        }
+    }
+
+    pub async fn get_balance(
+        &self,
+        address: Address,
+        block: Option<BlockNumber>,
+    ) -> web3::Result<U256> {
+        match self {
+            Web3Transport::Http(web3) => web3.eth().balance(address, block).await,
+            Web3Transport::WebSocket(web3) => web3.eth().balance(address, block).await,
+        }
+    }
+
+    pub async fn call(
+        &self,
+        to: Address,
+        data: Vec<u8>,
+        block: Option<BlockNumber>,
+    ) -> web3::Result<Bytes> {
+        let request = CallRequest {
+            to: Some(to),
+            data: Some(Bytes(data)),
+            ..CallRequest::default()
         };
-        let storage_content_str = format!("{:?}", storage_content);
+        let block = block.map(BlockId::Number);
+        match self {
+            Web3Transport::Http(web3) => web3.eth().call(request, block).await,
+            Web3Transport::WebSocket(web3) => web3.eth().call(request, block).await,
+        }
+    }
 
-        let contract_addr = if byte_low == 0 {
-            format!(
-                "0x{}",
-                &storage_content_str[(storage_content_str.len() - (byte_high + 1) * 2)..]
-            )
-        } else {
-            format!(
-                "0x{}",
-                &storage_content_str[(storage_content_str.len() - (byte_high + 1) * 2)
-                    ..(storage_content_str.len() - byte_low * 2)]
-            )
+    pub async fn get_transaction(&self, id: TransactionId) -> web3::Result<Option<Transaction>> {
+        match self {
+            Web3Transport::Http(web3) => web3.eth().transaction(id).await,
+            Web3Transport::WebSocket(web3) => web3.eth().transaction(id).await,
+        }
+    }
+
+    pub async fn get_transaction_receipt(
+        &self,
+        hash: H256,
+    ) -> web3::Result<Option<TransactionReceipt>> {
+        match self {
+            Web3Transport::Http(web3) => web3.eth().transaction_receipt(hash).await,
+            Web3Transport::WebSocket(web3) => web3.eth().transaction_receipt(hash).await,
+        }
+    }
+}
+
+/// Slot of `mapping[key]` for a mapping declared at `base`, `key` being the
+/// word the key is padded to
+pub(crate) fn mapping_slot(key: U256, base: U256) -> U256 {
+    let mut preimage = [0u8; 64];
+    key.to_big_endian(&mut preimage[..32]);
+    base.to_big_endian(&mut preimage[32..]);
+    U256::from_big_endian(&keccak256(&preimage))
+}
+
+/// Slot of `array[index]` for a dynamic array declared at `base`, whose
+/// elements span `elem_size` words
+pub(crate) fn array_element_slot(base: U256, index: U256, elem_size: u64) -> U256 {
+    let mut preimage = [0u8; 32];
+    base.to_big_endian(&mut preimage);
+    let start = U256::from_big_endian(&keccak256(&preimage));
+    // slots wrap around the storage space
+    start
+        .overflowing_add(index.overflowing_mul(elem_size.into()).0)
+        .0
+}
+
+/// Errors decoding a field packed in a storage word
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StorageWordError {
+    /// Byte range that is reversed, unparsable or beyond the 32 bytes of a word
+    InvalidRange(String),
+    /// Field of another width than the type decoded from it
+    Width { expected: usize, found: usize },
+    /// Field decoded as a bool holding neither 0 nor 1
+    NotBool(U256),
+}
+
+impl fmt::Display for StorageWordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageWordError::InvalidRange(msg) => write!(f, "Invalid byte range: {}", msg),
+            StorageWordError::Width { expected, found } => write!(
+                f,
+                "Field of {} bytes decoded as a {}-byte value",
+                found, expected
+            ),
+            StorageWordError::NotBool(value) => write!(f, "Not a bool: {:#x}", value),
+        }
+    }
+}
+
+impl Error for StorageWordError {}
+
+/// Bytes a field is packed in within a storage word, counted from the least
+/// significant byte as Gigahorse reports them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackedField {
+    byte_low: usize,
+    byte_high: usize,
+}
+
+impl PackedField {
+    /// An address in the low 20 bytes, as a slot holding only an address
+    pub const ADDRESS: PackedField = PackedField {
+        byte_low: 0,
+        byte_high: 19,
+    };
+
+    pub fn new(byte_low: usize, byte_high: usize) -> Result<Self, StorageWordError> {
+        if byte_low > byte_high || byte_high > 31 {
+            return Err(StorageWordError::InvalidRange(format!(
+                "{}..={}",
+                byte_low, byte_high
+            )));
+        }
+        Ok(PackedField {
+            byte_low,
+            byte_high,
+        })
+    }
+
+    /// Field from the decimal bounds of a Gigahorse relation
+    pub fn parse(byte_low: &str, byte_high: &str) -> Result<Self, StorageWordError> {
+        let bound = |bound: &str| {
+            usize::from_str(bound)
+                .map_err(|e| StorageWordError::InvalidRange(format!("{}: {}", bound, e)))
         };
-        let duration = start.elapsed();
-        info!("read storage consumes {:?}", duration);
-        Ok(contract_addr)
+        Self::new(bound(byte_low)?, bound(byte_high)?)
+    }
+
+    pub fn width(&self) -> usize {
+        self.byte_high - self.byte_low + 1
+    }
+}
+
+/// A storage word, decoded into the fields packed in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageWord(H256);
+
+impl From<H256> for StorageWord {
+    fn from(word: H256) -> Self {
+        StorageWord(word)
+    }
+}
+
+impl From<U256> for StorageWord {
+    fn from(word: U256) -> Self {
+        let mut bytes = [0u8; 32];
+        word.to_big_endian(&mut bytes);
+        StorageWord(H256(bytes))
+    }
+}
+
+impl StorageWord {
+    /// Big-endian bytes of `field`
+    pub fn bytes(&self, field: PackedField) -> &[u8] {
+        &self.0.as_bytes()[31 - field.byte_high..32 - field.byte_low]
+    }
+
+    pub fn uint(&self, field: PackedField) -> U256 {
+        U256::from_big_endian(self.bytes(field))
+    }
+
+    /// Address in `field`, or in the low 20 bytes of a field spanning the
+    /// whole word as Gigahorse reports an unpacked address variable
+    pub fn address(&self, field: PackedField) -> Result<Address, StorageWordError> {
+        if field.width() == 32 {
+            return self.address(PackedField::ADDRESS);
+        }
+        if field.width() != 20 {
+            return Err(StorageWordError::Width {
+                expected: 20,
+                found: field.width(),
+            });
+        }
+        Ok(Address::from_slice(self.bytes(field)))
+    }
+
+    pub fn bool(&self, field: PackedField) -> Result<bool, StorageWordError> {
+        match self.uint(field) {
+            value if value.is_zero() => Ok(false),
+            value if value == U256::one() => Ok(true),
+            value => Err(StorageWordError::NotBool(value)),
+        }
+    }
+
+    /// Address in the low 20 bytes, unless the word is empty there
+    pub fn nonzero_address(&self) -> Option<Address> {
+        self.address(PackedField::ADDRESS)
+            .ok()
+            .filter(|address| !address.is_zero())
+    }
+}
+
+/// Extract the address packed in `byte_low..=byte_high` of a storage word
+pub(crate) fn storage_word_to_addr(
+    storage_content: H256,
+    byte_low: &str,
+    byte_high: &str,
+) -> Result<Address, StorageWordError> {
+    let field = PackedField::parse(byte_low, byte_high)?;
+    StorageWord::from(storage_content).address(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_word_to_addr() {
+        let word =
+            H256::from_str("0x000000000000000000000000bb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c")
+                .unwrap();
+        let address: Address = "0xbb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c"
+            .parse()
+            .unwrap();
+        assert_eq!(storage_word_to_addr(word, "0", "19").unwrap(), address);
+        // an unpacked address variable spans the whole slot
+        assert_eq!(storage_word_to_addr(word, "0", "31").unwrap(), address);
+    }
+
+    #[test]
+    fn test_packed_slot() {
+        // address owner; bool paused; uint8 decimals; uint64 deadline;
+        // packed from the low bytes up
+        let word = StorageWord::from(
+            U256::from_str("00000000000065f5e1001201bb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c")
+                .unwrap(),
+        );
+        let owner = PackedField::new(0, 19).unwrap();
+        let paused = PackedField::new(20, 20).unwrap();
+        let decimals = PackedField::new(21, 21).unwrap();
+        let deadline = PackedField::new(22, 29).unwrap();
+        assert_eq!(
+            word.address(owner).unwrap(),
+            "0xbb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c"
+                .parse::<Address>()
+                .unwrap()
+        );
+        assert!(word.bool(paused).unwrap());
+        assert_eq!(word.uint(decimals), U256::from(0x12));
+        assert_eq!(word.uint(deadline), U256::from(0x65f5e100u64));
+        // the top bytes are unused
+        assert_eq!(word.uint(PackedField::new(30, 31).unwrap()), U256::zero());
+        assert_eq!(word.nonzero_address(), word.address(owner).ok());
+
+        // an address spanning the decimals and the deadline
+        assert_eq!(
+            word.address(PackedField::new(10, 29).unwrap()).unwrap(),
+            "0000000065f5e1001201bb4cdb9cbd36b01bd1cb"
+                .parse::<Address>()
+                .unwrap()
+        );
+        assert_eq!(
+            word.address(deadline),
+            Err(StorageWordError::Width {
+                expected: 20,
+                found: 8
+            })
+        );
+        assert_eq!(
+            word.bool(decimals),
+            Err(StorageWordError::NotBool(U256::from(0x12)))
+        );
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+        assert!(matches!(
+            PackedField::new(12, 32),
+            Err(StorageWordError::InvalidRange(_))
+        ));
+        assert!(matches!(
+            PackedField::new(20, 19),
+            Err(StorageWordError::InvalidRange(_))
+        ));
+        assert!(matches!(
+            PackedField::parse("0", "x"),
+            Err(StorageWordError::InvalidRange(_))
+        ));
+        assert_eq!(PackedField::parse("0", "19"), Ok(PackedField::ADDRESS));
+        assert!(storage_word_to_addr(H256::zero(), "12", "40").is_err());
+        assert_eq!(
+            storage_word_to_addr(H256::zero(), "0", "30"),
+            Err(StorageWordError::Width {
+                expected: 20,
+                found: 31
+            })
+        );
+        assert_eq!(StorageWord::from(H256::zero()).nonzero_address(), None);
+    }
+
+    #[test]
+    fn test_element_slots() {
+        // mapping(uint256 => address) at slot 1, key 2
+        assert_eq!(
+            mapping_slot(U256::from(2), U256::from(1)),
+            U256::from_str("d9d16d34ffb15ba3a3d852f0d403e2ce1d691fb54de27ac87cd2f993f3ec330f")
+                .unwrap()
+        );
+        // address[] at slot 2, element 0 at keccak256(2)
+        let start =
+            U256::from_str("405787fa12a823e0f2b7631cc41b3ba8828b3321ca811111fa75cd3aa3bb5ace")
+                .unwrap();
+        assert_eq!(array_element_slot(U256::from(2), U256::zero(), 1), start);
+        assert_eq!(
+            array_element_slot(U256::from(2), U256::from(3), 2),
+            start + U256::from(6)
+        );
+        assert_eq!(
+            array_element_slot(U256::from(2), U256::MAX, 1),
+            start - U256::one()
+        );
     }
 }
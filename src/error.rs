@@ -22,6 +22,14 @@ pub enum LydiaError {
     Json(serde_json::Error),
     /// Network/Web3 errors
     Network(String),
+    /// Chain state the provider does not have (e.g. missing from a fixture)
+    StateUnavailable(String),
+    /// Gigahorse toolchain or client not found or not executable
+    ToolchainMissing(String),
+    /// Decompilation or client analysis exceeded its time budget
+    DecompilerTimeout(String),
+    /// Souffle or the decompiler failed on a contract
+    SouffleCrash(String),
     /// General application errors
     General(String),
 }
@@ -36,6 +44,10 @@ impl fmt::Display for LydiaError {
             LydiaError::Io(err) => write!(f, "I/O error: {}", err),
             LydiaError::Json(err) => write!(f, "JSON error: {}", err),
             LydiaError::Network(msg) => write!(f, "Network error: {}", msg),
+            LydiaError::StateUnavailable(msg) => write!(f, "State unavailable: {}", msg),
+            LydiaError::ToolchainMissing(msg) => write!(f, "Gigahorse toolchain missing: {}", msg),
+            LydiaError::DecompilerTimeout(msg) => write!(f, "Decompiler timeout: {}", msg),
+            LydiaError::SouffleCrash(msg) => write!(f, "Souffle crash: {}", msg),
             LydiaError::General(msg) => write!(f, "Error: {}", msg),
         }
     }
@@ -77,9 +89,9 @@ pub type LydiaResult<T> = Result<T, LydiaError>;
 #[macro_export]
 macro_rules! lydia_error {
     ($msg:expr) => {
-        crate::error::LydiaError::General($msg.to_string())
+        $crate::error::LydiaError::General($msg.to_string())
     };
     ($fmt:expr, $($arg:tt)*) => {
-        crate::error::LydiaError::General(format!($fmt, $($arg)*))
+        $crate::error::LydiaError::General(format!($fmt, $($arg)*))
     };
 }
\ No newline at end of file
@@ -1,15 +1,17 @@
 use std::collections::HashSet;
-use std::{collections::HashMap, error::Error, path::Path};
+use std::{collections::HashMap, error::Error, path::Path, sync::Arc};
 
+use crate::config::defaults;
 use crate::contract::contract::Contract;
 use crate::contract::data_structure::{
     self, CallArgs, ExternalCall, FuncArgToSensitiveVar, TaintedCallArg,
 };
+use crate::graph::call_graph::CallGraph;
+use crate::outputter::result_structure::{OpCreation, SemanticFeatures};
+use crate::workspace::Workspace;
 use csv::{ReaderBuilder, StringRecord};
 use log::error;
 use serde::{Deserialize, Serialize};
-const TEMP_PATH: &str = "./gigahorse-toolchain/.temp/";
-const ANALYSIS: &str = "Leslie";
 
 #[derive(Debug, Clone)]
 pub struct ProgramPoint {
@@ -24,19 +26,19 @@ pub struct ProgramPoint {
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ReachableSiteInfo {
-    caller: String,
-    caller_callback_func_sign: String,
+    pub(crate) caller: String,
+    pub(crate) caller_callback_func_sign: String,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ReenterInfo {
-    reenter_target: String,
-    reenter_func_sign: String,
+    pub(crate) reenter_target: String,
+    pub(crate) reenter_func_sign: String,
 }
 
 #[allow(dead_code)]
 pub struct FlowAnalysis<'a> {
-    contracts: &'a HashMap<String, Contract>,
+    contracts: &'a HashMap<String, Arc<Contract>>,
     main_contract_sign_list: Vec<String>,
     external_call_in_func_signature: HashSet<String>,
     pub visited_contracts: HashSet<String>,
@@ -46,40 +48,78 @@ pub struct FlowAnalysis<'a> {
     attack_matrix: HashMap<String, bool>,
     victim_callback_info: HashMap<String, Vec<ReachableSiteInfo>>,
     attack_reenter_info: HashMap<String, Vec<ReenterInfo>>,
+    workspace: Workspace,
+    /// Code key of each analyzed address by phase: its creation code
+    /// (`true`) or its runtime code (`false`)
+    code_keys: HashMap<(String, bool), String>,
 }
 
 #[allow(dead_code)]
 impl<'a> FlowAnalysis<'a> {
     pub fn new(
-        contracts: &'a HashMap<String, Contract>,
+        contracts: &'a HashMap<String, Arc<Contract>>,
         main_contract_sign_list: Vec<String>,
         external_call_in_func_signature: HashSet<String>,
         visited_contracts: HashSet<String>,
         visited_funcs: HashSet<String>,
+        workspace: Workspace,
     ) -> Self {
+        let mut code_keys: HashMap<(String, bool), String> = HashMap::new();
+        for contract in contracts.values() {
+            let code_key = contract.code_key().to_string();
+            // a phase has a single code; the smallest key keeps the choice
+            // stable across runs should it not
+            code_keys
+                .entry((contract.logic_addr.clone(), *contract.is_createbin()))
+                .and_modify(|kept| {
+                    if code_key < *kept {
+                        kept.clone_from(&code_key);
+                    }
+                })
+                .or_insert(code_key);
+        }
         FlowAnalysis {
-            contracts: contracts,
-            main_contract_sign_list: main_contract_sign_list,
-            external_call_in_func_signature: external_call_in_func_signature,
-            visited_contracts: visited_contracts,
-            visited_funcs: visited_funcs,
+            contracts,
+            main_contract_sign_list,
+            external_call_in_func_signature,
+            visited_contracts,
+            visited_funcs,
             intra_callsigns: Vec::new(),
             sensitive_callsigns: Vec::new(),
             attack_matrix: HashMap::new(),
             victim_callback_info: HashMap::new(),
             attack_reenter_info: HashMap::new(),
+            workspace,
+            code_keys,
         }
     }
 
+    /// Leslie output of a contract, located through the code key it was analyzed under
+    fn relation_path(&self, contract_addr: &str, relation: &str) -> String {
+        self.relation_path_in(contract_addr, false, relation)
+    }
+
+    /// Same, preferring the analysis of the creation code when `creation`
+    /// and of the runtime code otherwise
+    fn relation_path_in(&self, contract_addr: &str, creation: bool, relation: &str) -> String {
+        let code_key = [creation, !creation]
+            .iter()
+            .find_map(|phase| self.code_keys.get(&(contract_addr.to_string(), *phase)))
+            .map(String::as_str)
+            .unwrap_or(contract_addr);
+        self.workspace
+            .relation_path(code_key, relation)
+            .to_string_lossy()
+            .into_owned()
+    }
+
     fn read_csv<T: From<StringRecord>>(
         &self,
         file_path: &str,
         data: &mut Vec<T>,
     ) -> Result<(), Box<dyn Error>> {
         if Path::new(&file_path).exists() {
-            let mut rdr = ReaderBuilder::new()
-                .delimiter(b'\t')
-                .from_path(&file_path)?;
+            let mut rdr = ReaderBuilder::new().delimiter(b'\t').from_path(file_path)?;
             for result in rdr.records() {
                 let record = result?;
                 data.push(T::from(record));
@@ -96,9 +136,9 @@ impl<'a> FlowAnalysis<'a> {
                 let mut br_analysis_df = Vec::new();
                 if let Err(err) = self
                     .read_csv::<data_structure::SensitiveOpOfBadRandomnessAfterExternalCall>(
-                        &format!(
-                            "{}{}/out/{}_SensitiveOpOfBadRandomnessAfterExternalCall.csv",
-                            TEMP_PATH, temp_address, ANALYSIS
+                        &self.relation_path(
+                            temp_address,
+                            "SensitiveOpOfBadRandomnessAfterExternalCall",
                         ),
                         &mut br_analysis_df,
                     )
@@ -113,7 +153,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     fn intraprocedural_dos_analysis(&mut self) -> bool {
@@ -124,10 +164,7 @@ impl<'a> FlowAnalysis<'a> {
                 let mut dos_analysis_df = Vec::new();
                 if let Err(err) = self
                     .read_csv::<data_structure::SensitiveOpOfDoSAfterExternalCall>(
-                        &format!(
-                            "{}{}/out/{}_SensitiveOpOfDoSAfterExternalCall.csv",
-                            TEMP_PATH, temp_address, ANALYSIS
-                        ),
+                        &self.relation_path(temp_address, "SensitiveOpOfDoSAfterExternalCall"),
                         &mut dos_analysis_df,
                     )
                 {
@@ -141,7 +178,72 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
+    }
+
+    /// Semantic features of the function `func_sign` of `contract_addr`,
+    /// e.g. the constructor of a created contract
+    pub fn semantic_features_of(&self, contract_addr: &str, func_sign: &str) -> SemanticFeatures {
+        let creation = func_sign == defaults::CREATEBIN_FUNC_SELECTOR;
+        SemanticFeatures {
+            op_creation: OpCreation {
+                op_multicreate: self.relation_has_func(
+                    contract_addr,
+                    creation,
+                    "Op_CreateInLoop",
+                    func_sign,
+                ),
+                op_solecreate: self.relation_has_func(
+                    contract_addr,
+                    creation,
+                    "Op_SoleCreate",
+                    func_sign,
+                ),
+            },
+            op_selfdestruct: self.relation_has_func(
+                contract_addr,
+                creation,
+                "Op_Selfdestruct",
+                func_sign,
+            ),
+            op_env: self.relation_has_func(
+                contract_addr,
+                creation,
+                "EnvVarFlowsToTaintedVar",
+                func_sign,
+            ),
+        }
+    }
+
+    /// Whether a relation keyed by function signature has a row of `func_sign`
+    fn relation_has_func(
+        &self,
+        contract_addr: &str,
+        creation: bool,
+        relation: &str,
+        func_sign: &str,
+    ) -> bool {
+        let loc = self.relation_path_in(contract_addr, creation, relation);
+        if !Path::new(&loc).exists() {
+            return false;
+        }
+        let rows = ReaderBuilder::new()
+            .delimiter(b'\t')
+            .has_headers(false)
+            .flexible(true)
+            .from_path(&loc)
+            .map(|mut rdr| {
+                rdr.records()
+                    .filter_map(|record| record.ok())
+                    .any(|record| record.get(0) == Some(func_sign))
+            });
+        match rows {
+            Ok(found) => found,
+            Err(err) => {
+                error!("Error reading CSV: {}", err);
+                false
+            }
+        }
     }
 
     // tainted op analysis
@@ -152,10 +254,7 @@ impl<'a> FlowAnalysis<'a> {
                 let temp_func_sign = key.split("_").collect::<Vec<&str>>()[3];
                 let mut df = Vec::new();
                 if let Err(err) = self.read_csv::<data_structure::EnvVarFlowsToTaintedVar>(
-                    &format!(
-                        "{}{}/out/{}_EnvVarFlowsToTaintedVar.csv",
-                        TEMP_PATH, temp_address, ANALYSIS
-                    ),
+                    &self.relation_path(temp_address, "EnvVarFlowsToTaintedVar"),
                     &mut df,
                 ) {
                     error!("Error reading CSV: {}", err);
@@ -168,7 +267,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     // other intra analysis
@@ -182,10 +281,7 @@ impl<'a> FlowAnalysis<'a> {
                 }
                 let mut op_multicreate_analysis_df = Vec::new();
                 if let Err(err) = self.read_csv::<data_structure::OpCreateInLoop>(
-                    &format!(
-                        "{}{}/out/{}_Op_CreateInLoop.csv",
-                        TEMP_PATH, temp_address, ANALYSIS
-                    ),
+                    &self.relation_path(temp_address, "Op_CreateInLoop"),
                     &mut op_multicreate_analysis_df,
                 ) {
                     error!("Error reading CSV: {}", err);
@@ -198,7 +294,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     pub fn op_solecreate_analysis(&self) -> bool {
@@ -211,7 +307,7 @@ impl<'a> FlowAnalysis<'a> {
                 }
                 let mut op_solecreate_analysis_df = Vec::new();
                 if let Err(err) = self.read_csv::<data_structure::OpSoleCreate>(
-                    &format!("{}{}/out/{}_Op_SoleCreate.csv", TEMP_PATH, temp_address, ANALYSIS),
+                    &self.relation_path(temp_address, "Op_SoleCreate"),
                     &mut op_solecreate_analysis_df,
                 ) {
                     error!("Error reading CSV: {}", err);
@@ -224,7 +320,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     pub fn op_selfdestruct_analysis(&self) -> bool {
@@ -237,10 +333,7 @@ impl<'a> FlowAnalysis<'a> {
                 }
                 let mut op_selfdestruct_analysis_df = Vec::new();
                 if let Err(err) = self.read_csv::<data_structure::OpSelfdestruct>(
-                    &format!(
-                        "{}{}/out/{}_Op_Selfdestruct.csv",
-                        TEMP_PATH, temp_address, ANALYSIS
-                    ),
+                    &self.relation_path(temp_address, "Op_Selfdestruct"),
                     &mut op_selfdestruct_analysis_df,
                 ) {
                     error!("Error reading CSV: {}", err);
@@ -253,7 +346,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     // external call related
@@ -264,10 +357,7 @@ impl<'a> FlowAnalysis<'a> {
                 let temp_func_sign = key.split("_").collect::<Vec<&str>>()[3];
                 let mut df = Vec::new();
                 if let Err(err) = self.read_csv::<data_structure::ExternalCallInHook>(
-                    &format!(
-                        "{}{}/out/{}_ExternalCallInHook.csv",
-                        TEMP_PATH, temp_address, ANALYSIS
-                    ),
+                    &self.relation_path(temp_address, "ExternalCallInHook"),
                     &mut df,
                 ) {
                     error!("Error reading CSV: {}", err);
@@ -280,7 +370,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     pub fn externalcall_infallback(&self) -> bool {
@@ -290,10 +380,7 @@ impl<'a> FlowAnalysis<'a> {
                 let temp_func_sign = key.split("_").collect::<Vec<&str>>()[3];
                 let mut df = Vec::new();
                 if let Err(err) = self.read_csv::<data_structure::ExternalCallInFallback>(
-                    &format!(
-                        "{}{}/out/{}_ExternalCallInFallback.csv",
-                        TEMP_PATH, temp_address, ANALYSIS
-                    ),
+                    &self.relation_path(temp_address, "ExternalCallInFallback"),
                     &mut df,
                 ) {
                     error!("Error reading CSV: {}", err);
@@ -306,7 +393,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     // reentrancy related
@@ -317,10 +404,7 @@ impl<'a> FlowAnalysis<'a> {
                 let temp_func_sign = key.split("_").collect::<Vec<&str>>()[3];
                 let mut df = Vec::new();
                 if let Err(err) = self.read_csv::<data_structure::DoubleCallToSameContract>(
-                    &format!(
-                        "{}{}/out/{}_DoubleCallToSameContract.csv",
-                        TEMP_PATH, temp_address, ANALYSIS
-                    ),
+                    &self.relation_path(temp_address, "DoubleCallToSameContract"),
                     &mut df,
                 ) {
                     error!("Error reading CSV: {}", err);
@@ -333,7 +417,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     pub fn double_call_to_same_contract_by_storage(&self) -> bool {
@@ -344,10 +428,7 @@ impl<'a> FlowAnalysis<'a> {
                 let mut df = Vec::new();
                 if let Err(err) = self
                     .read_csv::<data_structure::DoubleCallToSameContractByStorage>(
-                        &format!(
-                            "{}{}/out/{}_DoubleCallToSameContractByStorage.csv",
-                            TEMP_PATH, temp_address, ANALYSIS
-                        ),
+                        &self.relation_path(temp_address, "DoubleCallToSameContractByStorage"),
                         &mut df,
                     )
                 {
@@ -361,7 +442,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     pub fn preset_call_in_standard_erc20_transfer(&self) -> bool {
@@ -371,10 +452,7 @@ impl<'a> FlowAnalysis<'a> {
                 let temp_func_sign = key.split("_").collect::<Vec<&str>>()[3];
                 let mut df = Vec::new();
                 if let Err(err) = self.read_csv::<data_structure::CallInStandardTransfer>(
-                    &format!(
-                        "{}{}/out/{}_CallInStandardTransfer.csv",
-                        TEMP_PATH, temp_address, ANALYSIS
-                    ),
+                    &self.relation_path(temp_address, "CallInStandardTransfer"),
                     &mut df,
                 ) {
                     error!("Error reading CSV: {}", err);
@@ -387,7 +465,7 @@ impl<'a> FlowAnalysis<'a> {
                 return false;
             }
         }
-        return false;
+        false
     }
 
     fn spread_call_ret_func_ret(
@@ -400,10 +478,7 @@ impl<'a> FlowAnalysis<'a> {
         let mut func_ret_index = Vec::new();
         let mut call_ret_func_ret_df = Vec::new();
         if let Err(err) = self.read_csv::<data_structure::CallRetToFuncRet>(
-            &format!(
-                "{}{}/out/{}_Spread_CallRetToFuncRet.csv",
-                TEMP_PATH, contract_address, ANALYSIS
-            ),
+            &self.relation_path(contract_address, "Spread_CallRetToFuncRet"),
             &mut call_ret_func_ret_df,
         ) {
             error!("Error reading CSV: {}", err);
@@ -429,10 +504,7 @@ impl<'a> FlowAnalysis<'a> {
         let mut call_args = Vec::new();
         let mut call_ret_call_arg_df = Vec::new();
         if let Err(err) = self.read_csv::<data_structure::CallRetToCallArg>(
-            &format!(
-                "{}{}/out/{}_Spread_CallRetToCallArg.csv",
-                TEMP_PATH, contract_address, ANALYSIS
-            ),
+            &self.relation_path(contract_address, "Spread_CallRetToCallArg"),
             &mut call_ret_call_arg_df,
         ) {
             error!("Error reading CSV: {}", err);
@@ -460,10 +532,7 @@ impl<'a> FlowAnalysis<'a> {
         let mut call_args = Vec::new();
         let mut func_arg_call_arg_df = Vec::new();
         if let Err(err) = self.read_csv::<data_structure::FuncArgToCallArg>(
-            &format!(
-                "{}{}/out/{}_Spread_FuncArgToCallArg.csv",
-                TEMP_PATH, contract_address, ANALYSIS
-            ),
+            &self.relation_path(contract_address, "Spread_FuncArgToCallArg"),
             &mut func_arg_call_arg_df,
         ) {
             error!("Error reading CSV: {}", err);
@@ -491,10 +560,7 @@ impl<'a> FlowAnalysis<'a> {
         let mut call_args = Vec::new();
         let mut func_arg_callee_df = Vec::new();
         if let Err(err) = self.read_csv::<data_structure::FuncArgToCallee>(
-            &format!(
-                "{}{}/out/{}_Spread_FuncArgToCalleeVar.csv",
-                TEMP_PATH, contract_address, ANALYSIS
-            ),
+            &self.relation_path(contract_address, "Spread_FuncArgToCalleeVar"),
             &mut func_arg_callee_df,
         ) {
             error!("Error reading CSV: {}", err);
@@ -522,10 +588,7 @@ impl<'a> FlowAnalysis<'a> {
         let mut func_ret_index = Vec::new();
         let mut func_arg_func_ret_df = Vec::new();
         if let Err(err) = self.read_csv::<data_structure::FuncArgToFuncRet>(
-            &format!(
-                "{}{}/out/{}_Spread_CallRetToFuncRet.csv",
-                TEMP_PATH, contract_address, ANALYSIS
-            ),
+            &self.relation_path(contract_address, "Spread_CallRetToFuncRet"),
             &mut func_arg_func_ret_df,
         ) {
             error!("Error reading CSV: {}", err);
@@ -568,6 +631,7 @@ impl<'a> FlowAnalysis<'a> {
         addr
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn get_new_program_point(
         &self,
         caller: &str,
@@ -621,10 +685,7 @@ impl<'a> FlowAnalysis<'a> {
         let mut call_args = Vec::new();
         let mut tainted_call_arg_df = Vec::new();
         if let Err(err) = self.read_csv::<data_structure::TaintedCallArg>(
-            &format!(
-                "{}{}/out/{}_TaintedCallArg.csv",
-                TEMP_PATH, contract_addr, ANALYSIS
-            ),
+            &self.relation_path(contract_addr, "TaintedCallArg"),
             &mut tainted_call_arg_df,
         ) {
             error!("Error reading CSV: {}", err);
@@ -698,10 +759,7 @@ impl<'a> FlowAnalysis<'a> {
 
         let mut func_arg_to_sensitive_var_df: Vec<FuncArgToSensitiveVar> = Vec::new();
         if let Err(err) = self.read_csv::<data_structure::FuncArgToSensitiveVar>(
-            &format!(
-                "{}{}/out/{}_FuncArgToSensitiveVar.csv",
-                TEMP_PATH, contract_addr, ANALYSIS
-            ),
+            &self.relation_path(contract_addr, "FuncArgToSensitiveVar"),
             &mut func_arg_to_sensitive_var_df,
         ) {
             error!("Error reading CSV: {}", err);
@@ -807,11 +865,17 @@ impl<'a> FlowAnalysis<'a> {
         func_sign: &str,
         caller_func_sign: &str,
     ) -> Option<&Contract> {
-        let key = format!(
-            "{}_{}_{}_{}_{}",
-            caller, callsite, contract_addr, func_sign, caller_func_sign
+        // the callee may have run on several storages or calling frames;
+        // the smallest key keeps the choice stable across runs
+        let edge = format!(
+            "{}_",
+            CallGraph::edge_key(caller, callsite, contract_addr, func_sign, caller_func_sign)
         );
-        self.contracts.get(&key)
+        self.contracts
+            .iter()
+            .filter(|(key, _)| key.starts_with(&edge))
+            .min_by_key(|(key, _)| key.as_str())
+            .map(|(_, contract)| contract.as_ref())
     }
 
     fn is_same(&self, first: &ProgramPoint, second: &ProgramPoint) -> bool {
@@ -921,8 +985,7 @@ impl<'a> FlowAnalysis<'a> {
                         &child_contract.external_calls,
                     );
 
-                    if temp_result != None {
-                        let (_temp_caller, temp_logic_addr, temp_func_sign) = temp_result.unwrap();
+                    if let Some((_temp_caller, temp_logic_addr, temp_func_sign)) = temp_result {
                         next_program_points.push(self.get_new_program_point(
                             &program_point.target_contract_addr,
                             &call_arg.call_stmt,
@@ -1006,7 +1069,7 @@ impl<'a> FlowAnalysis<'a> {
                     reachable_site.insert(
                         program_point_sink.target_func_sign.clone(),
                         ReachableSiteInfo {
-                            caller: caller,
+                            caller,
                             caller_callback_func_sign: caller_func_sign,
                         },
                     );
@@ -1032,12 +1095,8 @@ impl<'a> FlowAnalysis<'a> {
             if !overlap.is_empty() {
                 for i in overlap {
                     // initialize
-                    victim_callback_info
-                        .entry(i.clone())
-                        .or_insert_with(Vec::new);
-                    attacker_reenter_info
-                        .entry(i.clone())
-                        .or_insert_with(Vec::new);
+                    victim_callback_info.entry(i.clone()).or_default();
+                    attacker_reenter_info.entry(i.clone()).or_default();
 
                     if let Some(site) = reachable_site.get(&i) {
                         let entry = victim_callback_info.entry(i.clone()).or_default();
@@ -1045,7 +1104,7 @@ impl<'a> FlowAnalysis<'a> {
                             entry.push(site.clone());
                         }
                     }
-                    for (_, contract) in self.contracts {
+                    for contract in self.contracts.values() {
                         if contract.func_sign.eq(&i) && contract.level == 0 {
                             for ec in &contract.external_calls {
                                 let res = ReenterInfo {
@@ -1082,6 +1141,7 @@ impl<'a> FlowAnalysis<'a> {
         (result, self.attack_matrix.clone())
     }
 
+    #[allow(clippy::type_complexity)]
     pub fn get_reen_info(
         &self,
     ) -> (
@@ -1099,3 +1159,146 @@ impl<'a> FlowAnalysis<'a> {
         &self.attack_matrix
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AnalysisSource;
+    use crate::contract::data_structure::CalleeResolution;
+    use crate::workspace::TestWorkspace;
+    use std::fs;
+
+    #[test]
+    fn test_transfer_reaches_child_contract() {
+        let test = TestWorkspace::new("flow-child");
+        let parent_addr = "0x0000000000000000000000000000000000000001";
+        let child_addr = "0x0000000000000000000000000000000000000002";
+        let parent_source = AnalysisSource {
+            workspace: test.workspace.clone(),
+            ..AnalysisSource::for_test(parent_addr)
+        };
+        let child_source = AnalysisSource {
+            caller: parent_addr.to_string(),
+            call_site: "0x1a".to_string(),
+            func_sign: "0xa9059cbb".to_string(),
+            caller_func_sign: "0x12345678".to_string(),
+            level: 1,
+            ..AnalysisSource::for_test(child_addr)
+        };
+        let call = ExternalCall {
+            target_logic_addr: child_addr.to_string(),
+            target_storage_addr: child_addr.to_string(),
+            target_func_sign: "0xa9059cbb".to_string(),
+            call_site: "0x1a".to_string(),
+            caller_func_sign: "0x12345678".to_string(),
+            caller_addr: parent_addr.to_string(),
+            caller_code_key: "parent".to_string(),
+            call_stmt: "0x1a".to_string(),
+            call_op: "CALL".to_string(),
+            resolution: CalleeResolution::Constant,
+        };
+        let contracts = HashMap::from([
+            (
+                CallGraph::source_key(&parent_source),
+                Arc::new(Contract::analyzed_for_test(
+                    &parent_source,
+                    "parent",
+                    vec![call],
+                )),
+            ),
+            (
+                CallGraph::source_key(&child_source),
+                Arc::new(Contract::analyzed_for_test(
+                    &child_source,
+                    "child",
+                    Vec::new(),
+                )),
+            ),
+        ]);
+        // the first argument of transfer flows to its first return value
+        let out_dir = test.workspace.out_dir("child");
+        fs::create_dir_all(&out_dir).unwrap();
+        fs::write(
+            out_dir.join("Leslie_Spread_CallRetToFuncRet.csv"),
+            "func_sign\tfunc_arg_index\tfunc_arg\tfunc_ret_index\tfunc_ret\n\
+             0xa9059cbb\t0\tv1\t0\tv2\n",
+        )
+        .unwrap();
+        let flow = FlowAnalysis::new(
+            &contracts,
+            Vec::new(),
+            HashSet::new(),
+            HashSet::new(),
+            HashSet::new(),
+            test.workspace.clone(),
+        );
+
+        let next = flow.transfer(&ProgramPoint {
+            caller_addr: parent_addr.to_string(),
+            call_site: "0x1a".to_string(),
+            caller_func_sign: "0x12345678".to_string(),
+            target_contract_addr: child_addr.to_string(),
+            target_func_sign: "0xa9059cbb".to_string(),
+            index: "0".to_string(),
+            program_point_type: "call_arg".to_string(),
+        });
+        assert_eq!(next.len(), 1);
+        assert_eq!(next[0].program_point_type, "func_ret");
+        assert_eq!(next[0].target_contract_addr, child_addr);
+        assert_eq!(next[0].index, "0");
+    }
+
+    #[test]
+    fn test_code_key_per_phase() {
+        let test = TestWorkspace::new("flow-phases");
+        let created = "0x0000000000000000000000000000000000000003";
+        let runtime = AnalysisSource {
+            workspace: test.workspace.clone(),
+            ..AnalysisSource::for_test(created)
+        };
+        let constructor = AnalysisSource {
+            func_sign: defaults::CREATEBIN_FUNC_SELECTOR.to_string(),
+            createbin: true,
+            ..runtime.clone()
+        };
+        let contracts = HashMap::from([
+            (
+                CallGraph::source_key(&runtime),
+                Arc::new(Contract::analyzed_for_test(&runtime, "runtime", Vec::new())),
+            ),
+            (
+                CallGraph::source_key(&constructor),
+                Arc::new(Contract::analyzed_for_test(
+                    &constructor,
+                    "ctor",
+                    Vec::new(),
+                )),
+            ),
+        ]);
+        // only the constructor creates a contract
+        let out_dir = test.workspace.out_dir("ctor");
+        fs::create_dir_all(&out_dir).unwrap();
+        fs::write(
+            out_dir.join("Leslie_Op_SoleCreate.csv"),
+            format!("{}\t0x30\n", defaults::CREATEBIN_FUNC_SELECTOR),
+        )
+        .unwrap();
+        let flow = FlowAnalysis::new(
+            &contracts,
+            Vec::new(),
+            HashSet::new(),
+            HashSet::new(),
+            HashSet::new(),
+            test.workspace.clone(),
+        );
+
+        assert_eq!(
+            flow.relation_path(created, "Op_SoleCreate"),
+            test.workspace
+                .relation_path("runtime", "Op_SoleCreate")
+                .to_string_lossy()
+        );
+        let features = flow.semantic_features_of(created, defaults::CREATEBIN_FUNC_SELECTOR);
+        assert!(features.op_creation.op_solecreate);
+    }
+}
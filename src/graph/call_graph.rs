@@ -1,23 +1,70 @@
-use crate::{config::AnalysisSource, contract::contract::Contract};
+use crate::config::{defaults, AnalysisSource, CallerEnv};
+use crate::contract::contract::Contract;
+use crate::contract::data_structure::CalleeResolution;
+use crate::graph::contract_store::ContractStore;
+use crate::graph::model::{CallGraphModel, Edge, Node};
+use crate::outputter::result_structure::Truncation;
+use log::debug;
 use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
 #[allow(dead_code)]
-pub struct CallGraph<'a> {
+pub struct CallGraph {
     output: String,
     visited_contracts: HashSet<String>,
     visited_funcs: HashSet<String>,
     pub max_level: i32,
     platform: String,
-    contracts: &'a mut HashMap<String, Contract>,
+    contracts: Arc<ContractStore>,
+    workers: Arc<Semaphore>,
+    /// Edges this graph descended into
+    visited_edges: HashSet<String>,
+    model: CallGraphModel,
+    limits: TraversalLimits,
+    truncation: Truncation,
 }
-impl<'a> CallGraph<'a> {
-    pub fn new(platform: String, contracts: &'a mut HashMap<String, Contract>) -> CallGraph {
+
+/// Bounds of the traversal from one entry function
+#[derive(Debug, Clone, Copy)]
+pub struct TraversalLimits {
+    /// Deepest call level followed
+    pub max_depth: i32,
+    /// Nodes after which no further calls are followed
+    pub max_nodes: usize,
+}
+
+/// The call through which a pending callee was reached
+struct PendingEdge {
+    from: usize,
+    call_site: String,
+    call_op: String,
+    resolution: CalleeResolution,
+    /// Nodes from the entry function down to the caller
+    path: Vec<usize>,
+}
+
+impl CallGraph {
+    /// `contracts` may be shared with graphs built concurrently; `workers`
+    /// bounds how many contracts are analyzed at the same time
+    pub fn new(
+        platform: String,
+        contracts: Arc<ContractStore>,
+        workers: Arc<Semaphore>,
+        limits: TraversalLimits,
+    ) -> CallGraph {
         CallGraph {
             output: String::new(),
             visited_contracts: HashSet::new(),
             visited_funcs: HashSet::new(),
             max_level: 0,
             platform,
-            contracts: contracts,
+            contracts,
+            workers,
+            visited_edges: HashSet::new(),
+            model: CallGraphModel::new(),
+            limits,
+            truncation: Truncation::default(),
         }
     }
 
@@ -33,14 +80,62 @@ impl<'a> CallGraph<'a> {
         &self.visited_funcs
     }
 
-    #[allow(unused_variables)]
+    pub fn get_model(&self) -> &CallGraphModel {
+        &self.model
+    }
+
+    /// Calls left unexplored because of the traversal limits
+    pub fn get_truncation(&self) -> &Truncation {
+        &self.truncation
+    }
+
+    /// Start analyzing the contract of `source` in the background, unless it
+    /// is analyzed already
+    fn spawn_analysis(
+        &self,
+        key: String,
+        source: &AnalysisSource,
+        analyses: &mut HashMap<String, JoinHandle<Arc<Contract>>>,
+    ) {
+        if self.visited_edges.contains(&key) || analyses.contains_key(&key) {
+            return;
+        }
+        let contracts = self.contracts.clone();
+        let workers = self.workers.clone();
+        let source = source.clone();
+        analyses.insert(
+            key.clone(),
+            tokio::spawn(async move { contracts.get_or_analyze(&key, &source, &workers).await }),
+        );
+    }
+
+    /// Walk the calls reachable from `source` depth first. Callees are
+    /// analyzed concurrently as soon as they are discovered, while the graph
+    /// is still assembled in traversal order, so the output does not depend
+    /// on which analysis finishes first.
     pub async fn construct_cross_contract_call_graph(
         &mut self,
         source: AnalysisSource,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut pending = vec![source];
+        let mut analyses = HashMap::new();
+        let result = self.traverse(source, &mut analyses).await;
+        // analyses of calls the traversal skipped in the end are not needed
+        for analysis in analyses.into_values() {
+            analysis.abort();
+        }
+        result
+    }
+
+    #[allow(unused_variables)]
+    async fn traverse(
+        &mut self,
+        source: AnalysisSource,
+        analyses: &mut HashMap<String, JoinHandle<Arc<Contract>>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.spawn_analysis(Self::source_key(&source), &source, analyses);
+        let mut pending = vec![(source, None)];
         // println!("{:?}", pending);
-        while let Some(temp) = pending.pop() {
+        while let Some((temp, via)) = pending.pop() {
             let index = pending.len();
             // println!("pending length: {}", index);
             // println!("current temp contract: {}", temp.logic_addr);
@@ -48,11 +143,8 @@ impl<'a> CallGraph<'a> {
                 self.max_level = temp.level;
             }
 
-            let temp_key = format!(
-                "{}_{}_{}_{}_{}",
-                temp.caller, temp.call_site, temp.logic_addr, temp.func_sign, temp.caller_func_sign
-            );
-            println!(
+            let temp_key = Self::source_key(&temp);
+            debug!(
                 "{:indent$}{}_{}_{call_site} -> {}_{}",
                 "",
                 temp.caller,
@@ -73,32 +165,65 @@ impl<'a> CallGraph<'a> {
                 indent = temp.level as usize
             ));
 
-            if self.contracts.contains_key(&temp_key) {
+            let node = self.model.add_node(Node::of(&temp));
+            let path = match via {
+                Some(PendingEdge {
+                    from,
+                    call_site,
+                    call_op,
+                    resolution,
+                    path,
+                }) => {
+                    self.model.add_edge(Edge {
+                        from,
+                        to: node,
+                        call_site,
+                        call_op,
+                        level: temp.level,
+                        resolution,
+                    });
+                    path
+                }
+                None => {
+                    self.model.add_root(node);
+                    Vec::new()
+                }
+            };
+
+            // a call back into a contract on the path closes a cycle; the
+            // same function re-entered is not followed again
+            if let Some(cycle) = self.model.cycle_closed_by(&path, node) {
+                self.model.add_cycle(cycle);
+            }
+            if path.contains(&node) {
+                continue;
+            }
+
+            if self.visited_edges.contains(&temp_key) {
                 continue;
             }
             self.visited_funcs.insert(temp.func_sign.clone());
             self.visited_contracts.insert(temp.logic_addr.clone());
 
-            let mut new_contract = Contract::new(
-                temp.platform.clone(),
-                temp.logic_addr.clone(),
-                temp.storage_addr.clone(),
-                temp.func_sign.clone(),
-                temp.block_number,
-                temp.caller.clone(),
-                temp.call_site.clone(),
-                temp.level,
-            );
-            if let Err(e) = &new_contract.analyze().await {
-                eprintln!("An error occurred during analysis: {}", e);
+            self.spawn_analysis(temp_key.clone(), &temp, analyses);
+            let new_contract = match analyses.remove(&temp_key) {
+                Some(analysis) => analysis.await?,
+                None => continue,
             };
-            self.contracts.insert(temp_key.clone(), new_contract);
+            self.model
+                .set_func_name(node, new_contract.func_name(&temp.func_sign));
+            self.visited_edges.insert(temp_key.clone());
 
-            for external_call in &self.contracts[&temp_key].external_calls {
+            let mut path = path;
+            path.push(node);
+            for external_call in &new_contract.external_calls {
                 if !external_call.target_logic_addr.is_empty()
                     && !external_call.target_storage_addr.is_empty()
                     && !external_call.target_func_sign.is_empty()
                 {
+                    if !self.within_limits(temp.level, pending.len()) {
+                        continue;
+                    }
                     let source = AnalysisSource {
                         platform: temp.platform.clone(),
                         logic_addr: external_call.target_logic_addr.clone(),
@@ -106,14 +231,197 @@ impl<'a> CallGraph<'a> {
                         func_sign: external_call.target_func_sign.clone(),
                         block_number: temp.block_number,
                         caller: external_call.caller_addr.clone(),
+                        caller_code_key: external_call.caller_code_key.clone(),
                         call_site: external_call.call_site.clone(),
                         level: temp.level + 1,
                         caller_func_sign: external_call.caller_func_sign.clone(),
+                        provider: temp.provider.clone(),
+                        workspace: temp.workspace.clone(),
+                        gigahorse: temp.gigahorse.clone(),
+                        decompile_cache: temp.decompile_cache.clone(),
+                        deployments: temp.deployments.clone(),
+                        // a delegated call is made on behalf of the caller
+                        caller_env: if external_call.call_op == "DELEGATECALL" {
+                            temp.caller_env.clone()
+                        } else {
+                            CallerEnv {
+                                sender: temp.caller.clone(),
+                                address: temp.storage_addr.clone(),
+                                origin: temp.caller_env.origin.clone(),
+                            }
+                        },
+                        // msg.value is kept by a DELEGATECALL, the value of
+                        // other calls is not recovered
+                        value: if external_call.call_op == "DELEGATECALL" {
+                            temp.value
+                        } else {
+                            None
+                        },
+                        createbin: false,
+                        // a delegated call runs on the caller's storage
+                        constructor_storage: if external_call.call_op == "DELEGATECALL" {
+                            temp.constructor_storage.clone()
+                        } else {
+                            Arc::default()
+                        },
+                    };
+                    self.spawn_analysis(Self::source_key(&source), &source, analyses);
+                    let via = PendingEdge {
+                        from: node,
+                        call_site: external_call.call_stmt.clone(),
+                        call_op: external_call.call_op.clone(),
+                        resolution: external_call.resolution.clone(),
+                        path: path.clone(),
                     };
-                    pending.push(source);
+                    pending.push((source, Some(via)));
                 }
             }
+            // contracts deployed here run their constructor as callees
+            for created in &new_contract.created_contracts {
+                let address = match &created.address {
+                    Some(address) => address,
+                    None => continue,
+                };
+                if !self.within_limits(temp.level, pending.len()) {
+                    continue;
+                }
+                let source = AnalysisSource {
+                    platform: temp.platform.clone(),
+                    logic_addr: address.clone(),
+                    storage_addr: address.clone(),
+                    func_sign: defaults::CREATEBIN_FUNC_SELECTOR.to_string(),
+                    block_number: temp.block_number,
+                    caller: temp.logic_addr.clone(),
+                    caller_code_key: new_contract.code_key().to_string(),
+                    call_site: created.create_site.clone(),
+                    level: temp.level + 1,
+                    caller_func_sign: created.func_sign.clone(),
+                    provider: temp.provider.clone(),
+                    workspace: temp.workspace.clone(),
+                    gigahorse: temp.gigahorse.clone(),
+                    decompile_cache: temp.decompile_cache.clone(),
+                    deployments: temp.deployments.clone(),
+                    caller_env: CallerEnv {
+                        sender: temp.storage_addr.clone(),
+                        address: address.clone(),
+                        origin: temp.caller_env.origin.clone(),
+                    },
+                    value: None,
+                    createbin: true,
+                    constructor_storage: Arc::default(),
+                };
+                self.spawn_analysis(Self::source_key(&source), &source, analyses);
+                let via = PendingEdge {
+                    from: node,
+                    call_site: created.create_site.clone(),
+                    call_op: created.opcode.clone(),
+                    resolution: CalleeResolution::Created,
+                    path: path.clone(),
+                };
+                pending.push((source, Some(via)));
+            }
         }
         Ok(())
     }
+
+    /// Whether a call from `level` is followed, counting the calls left out
+    fn within_limits(&mut self, level: i32, pending: usize) -> bool {
+        if level >= self.limits.max_depth {
+            self.truncation.calls_beyond_depth += 1;
+            return false;
+        }
+        if self.model.nodes.len() + pending >= self.limits.max_nodes {
+            self.truncation.calls_beyond_budget += 1;
+            return false;
+        }
+        true
+    }
+
+    /// Key of the call edge `source` is reached through, which the analyzed
+    /// contracts are stored under. The analysis of the callee depends on the
+    /// storage it runs on and on its calling frame as well, e.g. a
+    /// DELEGATECALL reached through different proxies.
+    pub(crate) fn source_key(source: &AnalysisSource) -> String {
+        format!(
+            "{}_{}_{}_{}_{}_{}",
+            Self::edge_key(
+                &source.caller,
+                &source.call_site,
+                &source.logic_addr,
+                &source.func_sign,
+                &source.caller_func_sign
+            ),
+            source.storage_addr,
+            source.createbin,
+            source.caller_env.sender,
+            source.caller_env.address,
+            source.caller_env.origin
+        )
+    }
+
+    /// Front of the key of every analysis reached from `caller` at
+    /// `call_site`, whatever storage and calling frame it ran in
+    pub(crate) fn edge_key(
+        caller: &str,
+        call_site: &str,
+        logic_addr: &str,
+        func_sign: &str,
+        caller_func_sign: &str,
+    ) -> String {
+        format!(
+            "{}_{}_{}_{}_{}",
+            caller, call_site, logic_addr, func_sign, caller_func_sign
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_key_separates_contexts() {
+        let implementation = "0x0000000000000000000000000000000000000001";
+        let through_first = AnalysisSource {
+            storage_addr: "0x00000000000000000000000000000000000000a1".to_string(),
+            ..AnalysisSource::for_test(implementation)
+        };
+        let through_second = AnalysisSource {
+            storage_addr: "0x00000000000000000000000000000000000000a2".to_string(),
+            ..through_first.clone()
+        };
+        let constructor = AnalysisSource {
+            createbin: true,
+            ..through_first.clone()
+        };
+        let other_sender = AnalysisSource {
+            caller_env: CallerEnv {
+                sender: "0x00000000000000000000000000000000000000b1".to_string(),
+                ..CallerEnv::default()
+            },
+            ..through_first.clone()
+        };
+        let keys: HashSet<String> = [&through_first, &through_second, &constructor, &other_sender]
+            .into_iter()
+            .map(CallGraph::source_key)
+            .collect();
+        assert_eq!(keys.len(), 4);
+        assert_eq!(
+            CallGraph::source_key(&through_first),
+            CallGraph::source_key(&through_first.clone())
+        );
+        // the flow analysis reads caller, call site, address and selector
+        // from the front of the key
+        let key = CallGraph::source_key(&through_first);
+        let parts: Vec<&str> = key.split('_').collect();
+        assert_eq!(parts[2], implementation);
+        assert_eq!(parts[3], "0x12345678");
+        assert!(key.starts_with(&CallGraph::edge_key(
+            &through_first.caller,
+            &through_first.call_site,
+            implementation,
+            "0x12345678",
+            &through_first.caller_func_sign
+        )));
+    }
 }
@@ -0,0 +1,83 @@
+//! Analyzed contracts shared by concurrently built call graphs
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OnceCell, Semaphore};
+
+use crate::config::AnalysisSource;
+use crate::contract::contract::Contract;
+
+/// Contracts keyed by the call edge they were reached through. Every edge is
+/// analyzed once, whichever call graph reaches it first; the others wait for
+/// and reuse that analysis.
+#[derive(Default)]
+pub struct ContractStore {
+    contracts: Mutex<HashMap<String, Arc<OnceCell<Arc<Contract>>>>>,
+}
+
+impl ContractStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Contract reached through the edge `key`, analyzing it from `source`
+    /// once a worker is free if no graph did so yet
+    pub async fn get_or_analyze(
+        &self,
+        key: &str,
+        source: &AnalysisSource,
+        workers: &Semaphore,
+    ) -> Arc<Contract> {
+        let cell = self
+            .contracts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(key.to_string())
+            .or_default()
+            .clone();
+        cell.get_or_init(|| async {
+            let _permit = workers.acquire().await;
+            let mut contract = Contract::from_source(source);
+            if let Err(e) = contract.analyze().await {
+                eprintln!("An error occurred during analysis: {}", e);
+            }
+            Arc::new(contract)
+        })
+        .await
+        .clone()
+    }
+
+    /// All analyzed contracts by edge key
+    pub fn contracts(&self) -> HashMap<String, Arc<Contract>> {
+        self.contracts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter_map(|(key, cell)| Some((key.clone(), cell.get()?.clone())))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[tokio::test]
+    async fn test_edge_analyzed_once() {
+        let store = ContractStore::new();
+        let workers = Semaphore::new(2);
+        let first = AnalysisSource::for_test("0x0000000000000000000000000000000000000001");
+        let second = AnalysisSource::for_test("0x0000000000000000000000000000000000000002");
+
+        let (a, b, c) = tokio::join!(
+            store.get_or_analyze("first", &first, &workers),
+            store.get_or_analyze("first", &first, &workers),
+            store.get_or_analyze("second", &second, &workers),
+        );
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+        // the mock has no code, so the callee is reported unresolved
+        assert_eq!(a.unresolved_callees.len(), 1);
+        assert_eq!(store.contracts().len(), 2);
+    }
+}
@@ -1 +1,3 @@
 pub mod call_graph;
+pub mod contract_store;
+pub mod model;
@@ -0,0 +1,247 @@
+//! Typed model of a cross-contract call graph
+//!
+//! Nodes are functions of a contract executing in a storage context; edges
+//! are the external calls between them. The model is serialized into the
+//! analysis result, so paths can be queried without parsing `call_paths`.
+
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::AnalysisSource;
+use crate::contract::data_structure::CalleeResolution;
+
+/// A function of the code at `address` running on the storage of
+/// `storage_addr`, which differs from `address` behind a DELEGATECALL
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+    pub address: String,
+    pub func_sign: String,
+    pub storage_addr: String,
+    /// Textual signature of `func_sign`, when Gigahorse knows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub func_name: Option<String>,
+}
+
+impl Node {
+    pub fn of(source: &AnalysisSource) -> Self {
+        Node {
+            address: source.logic_addr.clone(),
+            func_sign: source.func_sign.clone(),
+            storage_addr: source.storage_addr.clone(),
+            func_name: None,
+        }
+    }
+
+    fn key(&self) -> (String, String, String) {
+        (
+            self.address.clone(),
+            self.func_sign.clone(),
+            self.storage_addr.clone(),
+        )
+    }
+}
+
+/// An external call from node `from` to node `to`, indexing `CallGraphModel::nodes`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    /// Call statement in the code of the caller
+    pub call_site: String,
+    /// CALL, STATICCALL or DELEGATECALL, or CREATE and CREATE2 deploying
+    /// the callee
+    pub call_op: String,
+    /// Call depth of the callee
+    pub level: i32,
+    pub resolution: CalleeResolution,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CallGraphModel {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+    /// Entry points of the analyzed transaction
+    pub roots: Vec<usize>,
+    /// Call paths returning to a contract already on the path, from its
+    /// first occurrence to the node calling back into it
+    pub cycles: Vec<Vec<usize>>,
+    #[serde(skip)]
+    node_index: HashMap<(String, String, String), usize>,
+    #[serde(skip)]
+    edge_set: HashSet<Edge>,
+}
+
+impl CallGraphModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Index of `node`, added unless already present
+    pub fn add_node(&mut self, node: Node) -> usize {
+        if let Some(&index) = self.node_index.get(&node.key()) {
+            if self.nodes[index].func_name.is_none() {
+                self.nodes[index].func_name = node.func_name;
+            }
+            return index;
+        }
+        let index = self.nodes.len();
+        self.node_index.insert(node.key(), index);
+        self.nodes.push(node);
+        index
+    }
+
+    pub fn set_func_name(&mut self, node: usize, func_name: Option<&str>) {
+        if let Some(name) = func_name {
+            self.nodes[node].func_name = Some(name.to_string());
+        }
+    }
+
+    pub fn add_root(&mut self, node: usize) {
+        if !self.roots.contains(&node) {
+            self.roots.push(node);
+        }
+    }
+
+    /// The cycle formed when `path` continues to `node`, i.e. `path` from
+    /// the first node sharing the address of `node`, followed by `node`. A
+    /// contract calling itself (`this.f()`) does not close a cycle.
+    pub fn cycle_closed_by(&self, path: &[usize], node: usize) -> Option<Vec<usize>> {
+        let address = &self.nodes[node].address;
+        if self.nodes[*path.last()?]
+            .address
+            .eq_ignore_ascii_case(address)
+        {
+            return None;
+        }
+        let start = path
+            .iter()
+            .position(|&n| self.nodes[n].address.eq_ignore_ascii_case(address))?;
+        let mut cycle = path[start..].to_vec();
+        cycle.push(node);
+        Some(cycle)
+    }
+
+    pub fn add_cycle(&mut self, cycle: Vec<usize>) {
+        if !self.cycles.contains(&cycle) {
+            self.cycles.push(cycle);
+        }
+    }
+
+    /// Add `edge` unless the same call is already recorded
+    pub fn add_edge(&mut self, edge: Edge) {
+        if self.edge_set.insert(edge.clone()) {
+            self.edges.push(edge);
+        }
+    }
+
+    /// Add the nodes and edges of `other`, keeping those already present
+    pub fn merge(&mut self, other: &CallGraphModel) {
+        let mapping: Vec<usize> = other
+            .nodes
+            .iter()
+            .map(|node| self.add_node(node.clone()))
+            .collect();
+        for &root in &other.roots {
+            self.add_root(mapping[root]);
+        }
+        for cycle in &other.cycles {
+            self.add_cycle(cycle.iter().map(|&node| mapping[node]).collect());
+        }
+        for edge in &other.edges {
+            self.add_edge(Edge {
+                from: mapping[edge.from],
+                to: mapping[edge.to],
+                ..edge.clone()
+            });
+        }
+    }
+
+    /// Outgoing edges of `node`
+    pub fn callees(&self, node: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(address: &str, func_sign: &str) -> Node {
+        Node {
+            address: address.to_string(),
+            func_sign: func_sign.to_string(),
+            storage_addr: address.to_string(),
+            func_name: None,
+        }
+    }
+
+    fn edge(from: usize, to: usize, call_site: &str) -> Edge {
+        Edge {
+            from,
+            to,
+            call_site: call_site.to_string(),
+            call_op: "CALL".to_string(),
+            level: 1,
+            resolution: CalleeResolution::Constant,
+        }
+    }
+
+    #[test]
+    fn test_merge_remaps_nodes() {
+        let mut first = CallGraphModel::new();
+        let a = first.add_node(node("0xa", "0x1"));
+        let b = first.add_node(node("0xb", "0x2"));
+        first.add_root(a);
+        first.add_edge(edge(a, b, "0x10"));
+        first.add_edge(edge(a, b, "0x10"));
+        assert_eq!(first.edges.len(), 1);
+
+        let mut second = CallGraphModel::new();
+        let c = second.add_node(node("0xc", "0x3"));
+        let b2 = second.add_node(node("0xb", "0x2"));
+        second.add_root(c);
+        second.add_edge(edge(c, b2, "0x20"));
+
+        first.merge(&second);
+        assert_eq!(first.nodes.len(), 3);
+        assert_eq!(first.roots, vec![0, 2]);
+        assert_eq!(first.edges[1].from, 2);
+        assert_eq!(first.edges[1].to, b);
+        assert_eq!(first.callees(a).count(), 1);
+
+        first.add_cycle(vec![2, 1, 2]);
+        let json = serde_json::to_value(&first).unwrap();
+        assert_eq!(json["edges"][0]["resolution"], "constant");
+        assert!(json.get("node_index").is_none());
+    }
+
+    #[test]
+    fn test_cycle_closed_by() {
+        let mut graph = CallGraphModel::new();
+        let attack = graph.add_node(node("0xa", "0x1"));
+        let withdraw = graph.add_node(node("0xb", "0x2"));
+        let fallback = graph.add_node(node("0xA", "0x0"));
+        let oracle = graph.add_node(node("0xc", "0x3"));
+
+        // attacker -> victim -> attacker, addresses compared case-insensitively
+        assert_eq!(
+            graph.cycle_closed_by(&[attack, withdraw], fallback),
+            Some(vec![attack, withdraw, fallback])
+        );
+        assert_eq!(
+            graph.cycle_closed_by(&[attack, withdraw, fallback], withdraw),
+            Some(vec![withdraw, fallback, withdraw])
+        );
+        assert_eq!(graph.cycle_closed_by(&[attack, withdraw], oracle), None);
+        assert_eq!(graph.cycle_closed_by(&[], attack), None);
+
+        // victim.withdraw -> this.balanceOf is a self-call, not reentrancy
+        let balance_of = graph.add_node(node("0xb", "0x4"));
+        assert_eq!(graph.cycle_closed_by(&[attack, withdraw], balance_of), None);
+        assert_eq!(
+            graph.cycle_closed_by(&[withdraw, oracle], balance_of),
+            Some(vec![withdraw, oracle, balance_of])
+        );
+    }
+}
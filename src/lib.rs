@@ -4,13 +4,17 @@
 //! to detect malicious patterns and attack vectors.
 
 pub mod analysis;
+pub mod chain;
 pub mod config;
 pub mod contract;
 pub mod error;
 pub mod flow;
 pub mod graph;
 pub mod outputter;
+pub mod provider;
+pub mod transaction;
 pub mod utils;
+pub mod workspace;
 
 // Re-export commonly used types
 pub use crate::analysis::AnalysisEngine;
@@ -3,24 +3,13 @@
 //! A Rust-based tool for analyzing Ethereum smart contracts to detect
 //! malicious patterns and attack vectors using the Gigahorse toolchain.
 
-// Module declarations
-mod analysis;
-mod config;
-mod contract;
-mod error;
-mod flow;
-mod graph;
-mod outputter;
-mod utils;
-
 // Internal imports
-use crate::analysis::AnalysisEngine;
-use crate::config::Config;
-use crate::error::LydiaResult;
-use crate::utils::{init_logging, save_results_to_file};
+use lydia::utils::{init_logging, save_graph_to_file, save_results_to_file};
+use lydia::transaction::TxTarget;
+use lydia::{provider, AnalysisEngine, Config, LydiaResult};
 
 // External imports
-use log::{error, info};
+use log::{error, info, warn};
 
 #[tokio::main]
 async fn main() {
@@ -37,23 +26,44 @@ async fn main() {
 /// Main application logic
 async fn run() -> LydiaResult<()> {
     // Parse configuration from command line arguments
-    let config = Config::from_args()?;
+    let mut config = Config::from_args()?;
     config.validate()?;
+    // every run decompiles into a scratch directory of its own
+    config.workspace = config.workspace.prepare()?.isolate()?;
+    let provider = provider::from_config(&config).await?;
+    if let Some(hash) = config.tx_hash {
+        info!("Fetching transaction {:?}", hash);
+        TxTarget::fetch(provider.as_ref(), hash).await?.apply(&mut config)?;
+        config.validate()?;
+    }
     
     info!("Starting Lydia analysis...");
     info!("Logic address: {}", config.logic_address);
     info!("Storage address: {}", config.storage_address);
     info!("Platform: {}", config.platform);
     info!("Block number: {}", config.block_number);
+    info!("Workspace: {:?}", config.workspace);
+    if config.offline {
+        info!("Offline mode: no RPC endpoint will be queried");
+    }
 
     // Create and run the analysis engine
-    let engine = AnalysisEngine::new(config.clone());
-    let result = engine.analyze().await?;
+    let engine = AnalysisEngine::new(config.clone(), provider);
+    let result = engine.analyze().await;
+    if !config.keep_temp {
+        if let Err(e) = config.workspace.cleanup() {
+            warn!("Failed to remove {}: {}", config.workspace.scratch_dir.display(), e);
+        }
+    }
+    let result = result?;
     
     // Display results
     println!("{:#?}", result);
     
     // Save results to file
+    if let Some(format) = config.graph_format {
+        save_graph_to_file(&config.logic_address, &result, format)?;
+    }
     save_results_to_file(&config.logic_address, result)?;
     
     info!("Analysis completed successfully!");
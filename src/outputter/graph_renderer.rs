@@ -0,0 +1,289 @@
+//! Rendering of the merged call graph as Graphviz DOT, Mermaid or JSON
+//!
+//! Edges are styled by call opcode, and the nodes and edges behind a
+//! reentrancy finding are highlighted.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::error::LydiaResult;
+use crate::graph::model::{CallGraphModel, Edge, Node};
+use crate::outputter::result_structure::{PathInfo, Result as AnalysisResult};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+impl GraphFormat {
+    pub const NAMES: [&'static str; 3] = ["dot", "mermaid", "json"];
+
+    /// Extension of the file the graph is saved to
+    pub fn extension(&self) -> &'static str {
+        match self {
+            GraphFormat::Dot => "dot",
+            GraphFormat::Mermaid => "mmd",
+            GraphFormat::Json => "graph.json",
+        }
+    }
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            "json" => Ok(GraphFormat::Json),
+            other => Err(format!("unknown graph format {}", other)),
+        }
+    }
+}
+
+/// Nodes and edges (by index) lying on a reentrancy path
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Highlight {
+    pub nodes: BTreeSet<usize>,
+    pub edges: BTreeSet<usize>,
+}
+
+impl Highlight {
+    /// The entry function of each reentrancy finding, the victim functions
+    /// calling back into the attacker, and the attacker's re-entered
+    /// targets, with the edges between them
+    pub fn reentrancy(graph: &CallGraphModel, paths: &HashMap<String, PathInfo>) -> Self {
+        let matches = |node: &Node, address: &str, func_sign: &str| {
+            node.address.eq_ignore_ascii_case(address) && node.func_sign == func_sign
+        };
+        let mut highlight = Highlight::default();
+        for (func_sign, path) in paths {
+            for (index, node) in graph.nodes.iter().enumerate() {
+                let entry = graph.roots.contains(&index) && &node.func_sign == func_sign;
+                let victim = path
+                    .victim_call
+                    .iter()
+                    .any(|site| matches(node, &site.caller, &site.caller_callback_func_sign));
+                let reentered = path
+                    .attacker_reenter
+                    .iter()
+                    .any(|target| matches(node, &target.reenter_target, &target.reenter_func_sign));
+                if entry || victim || reentered {
+                    highlight.nodes.insert(index);
+                }
+            }
+        }
+        for (index, edge) in graph.edges.iter().enumerate() {
+            if highlight.nodes.contains(&edge.from) && highlight.nodes.contains(&edge.to) {
+                highlight.edges.insert(index);
+            }
+        }
+        highlight
+    }
+}
+
+/// Render the call graph of `result` in `format`
+pub fn render(result: &AnalysisResult, format: GraphFormat) -> LydiaResult<String> {
+    let graph = &result.call_graph;
+    let highlight = Highlight::reentrancy(graph, &result.reentrancy_path_info);
+    Ok(match format {
+        GraphFormat::Dot => render_dot(graph, &highlight),
+        GraphFormat::Mermaid => render_mermaid(graph, &highlight),
+        GraphFormat::Json => {
+            #[derive(Serialize)]
+            struct Rendered<'a> {
+                #[serde(flatten)]
+                graph: &'a CallGraphModel,
+                reentrancy: &'a Highlight,
+            }
+            serde_json::to_string_pretty(&Rendered {
+                graph,
+                reentrancy: &highlight,
+            })?
+        }
+    })
+}
+
+fn label(node: &Node) -> Vec<String> {
+    let mut lines = vec![node.address.clone()];
+    match &node.func_name {
+        Some(name) => lines.push(format!("{} {}", node.func_sign, name)),
+        None => lines.push(node.func_sign.clone()),
+    }
+    if !node.storage_addr.eq_ignore_ascii_case(&node.address) {
+        lines.push(format!("storage {}", node.storage_addr));
+    }
+    lines
+}
+
+fn edge_label(edge: &Edge) -> String {
+    format!("{} {}", edge.call_op, edge.call_site)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn render_dot(graph: &CallGraphModel, highlight: &Highlight) -> String {
+    let mut out = String::from("digraph call_graph {\n    node [shape=box, fontname=monospace];\n");
+    for (index, node) in graph.nodes.iter().enumerate() {
+        let mut attrs = format!(
+            "label=\"{}\"",
+            label(node)
+                .iter()
+                .map(|line| escape(line))
+                .collect::<Vec<_>>()
+                .join("\\n")
+        );
+        if graph.roots.contains(&index) {
+            attrs.push_str(", peripheries=2");
+        }
+        if highlight.nodes.contains(&index) {
+            attrs.push_str(", color=red, fontcolor=red");
+        }
+        let _ = writeln!(out, "    n{} [{}];", index, attrs);
+    }
+    for (index, edge) in graph.edges.iter().enumerate() {
+        let style = match edge.call_op.as_str() {
+            "DELEGATECALL" => "bold",
+            "STATICCALL" => "dashed",
+            _ => "solid",
+        };
+        let mut attrs = format!("label=\"{}\", style={}", escape(&edge_label(edge)), style);
+        if highlight.edges.contains(&index) {
+            attrs.push_str(", color=red, fontcolor=red, penwidth=2");
+        }
+        let _ = writeln!(out, "    n{} -> n{} [{}];", edge.from, edge.to, attrs);
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_mermaid(graph: &CallGraphModel, highlight: &Highlight) -> String {
+    let mut out = String::from("flowchart TD\n");
+    for (index, node) in graph.nodes.iter().enumerate() {
+        let text = label(node)
+            .iter()
+            .map(|line| line.replace('"', "#quot;"))
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        if graph.roots.contains(&index) {
+            let _ = writeln!(out, "    n{}([\"{}\"])", index, text);
+        } else {
+            let _ = writeln!(out, "    n{}[\"{}\"]", index, text);
+        }
+    }
+    for edge in &graph.edges {
+        let arrow = match edge.call_op.as_str() {
+            "DELEGATECALL" => "==>",
+            "STATICCALL" => "-.->",
+            _ => "-->",
+        };
+        let _ = writeln!(
+            out,
+            "    n{} {}|\"{}\"| n{}",
+            edge.from,
+            arrow,
+            edge_label(edge),
+            edge.to
+        );
+    }
+    for index in &highlight.nodes {
+        let _ = writeln!(out, "    style n{} stroke:red,stroke-width:2px", index);
+    }
+    for index in &highlight.edges {
+        let _ = writeln!(out, "    linkStyle {} stroke:red,stroke-width:2px", index);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::data_structure::CalleeResolution;
+    use crate::flow::flow_analysis::{ReachableSiteInfo, ReenterInfo};
+
+    fn node(address: &str, func_sign: &str) -> Node {
+        Node {
+            address: address.to_string(),
+            func_sign: func_sign.to_string(),
+            storage_addr: address.to_string(),
+            func_name: None,
+        }
+    }
+
+    fn edge(from: usize, to: usize, call_op: &str) -> Edge {
+        Edge {
+            from,
+            to,
+            call_site: "0x1a".to_string(),
+            call_op: call_op.to_string(),
+            level: 1,
+            resolution: CalleeResolution::Constant,
+        }
+    }
+
+    /// attacker.attack() calls victim.withdraw(), which calls back into
+    /// attacker.fallback; a static call to an oracle is off the path
+    fn reentrancy_graph() -> (CallGraphModel, HashMap<String, PathInfo>) {
+        let mut graph = CallGraphModel::new();
+        let attacker = graph.add_node(node("0xa", "0x11111111"));
+        let victim = graph.add_node(node("0xb", "0x22222222"));
+        let oracle = graph.add_node(node("0xc", "0x33333333"));
+        graph.add_root(attacker);
+        graph.add_edge(edge(attacker, victim, "CALL"));
+        graph.add_edge(edge(attacker, oracle, "STATICCALL"));
+        let paths = HashMap::from([(
+            "0x11111111".to_string(),
+            PathInfo {
+                victim_call: vec![ReachableSiteInfo {
+                    caller: "0xB".to_string(),
+                    caller_callback_func_sign: "0x22222222".to_string(),
+                }],
+                attacker_reenter: vec![ReenterInfo {
+                    reenter_target: "0xb".to_string(),
+                    reenter_func_sign: "0x22222222".to_string(),
+                }],
+            },
+        )]);
+        (graph, paths)
+    }
+
+    #[test]
+    fn test_reentrancy_highlight() {
+        let (graph, paths) = reentrancy_graph();
+        let highlight = Highlight::reentrancy(&graph, &paths);
+        assert_eq!(highlight.nodes, BTreeSet::from([0, 1]));
+        assert_eq!(highlight.edges, BTreeSet::from([0]));
+    }
+
+    #[test]
+    fn test_render_dot_and_mermaid() {
+        let (mut graph, paths) = reentrancy_graph();
+        graph.set_func_name(1, Some("withdraw(uint256)"));
+        let highlight = Highlight::reentrancy(&graph, &paths);
+
+        let dot = render_dot(&graph, &highlight);
+        assert!(dot.contains("n1 [label=\"0xb\\n0x22222222 withdraw(uint256)\", color=red"));
+        assert!(dot.contains("n0 -> n1 [label=\"CALL 0x1a\", style=solid, color=red"));
+        assert!(dot.contains("n0 -> n2 [label=\"STATICCALL 0x1a\", style=dashed];"));
+
+        let mermaid = render_mermaid(&graph, &highlight);
+        assert!(mermaid.contains("n0([\"0xa<br/>0x11111111\"])"));
+        assert!(mermaid.contains("n0 -.->|\"STATICCALL 0x1a\"| n2"));
+        assert!(mermaid.contains("linkStyle 0 stroke:red"));
+        assert!(!mermaid.contains("linkStyle 1"));
+    }
+
+    #[test]
+    fn test_graph_format() {
+        assert_eq!("DOT".parse::<GraphFormat>(), Ok(GraphFormat::Dot));
+        assert_eq!(GraphFormat::Mermaid.extension(), "mmd");
+        assert!("svg".parse::<GraphFormat>().is_err());
+    }
+}
@@ -1 +1,2 @@
+pub mod graph_renderer;
 pub mod result_structure;
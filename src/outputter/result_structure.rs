@@ -1,8 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use serde::{Deserialize, Serialize};
+use web3::types::U256;
 
+use crate::contract::data_structure::CalleeResolution;
+use crate::contract::proxy::ProxyLayout;
 use crate::flow::flow_analysis::{ReachableSiteInfo, ReenterInfo};
+use crate::graph::model::{CallGraphModel, Node};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Result {
@@ -12,10 +16,15 @@ pub struct Result {
     pub analysis_loc: String,
     pub platform: String,
     pub block_number: u64,
+    pub tx_context: TxContext,
     pub time: Option<String>,
     pub semantic_features: SemanticFeatures,
     pub external_call: ExternalCall,
     pub call_paths: Vec<String>,
+    pub call_graph: CallGraphModel,
+    /// Calls returning to a contract already on their path
+    pub cycles: Vec<CallCycle>,
+    pub truncation: Truncation,
     pub visited_contracts: Vec<String>,
     pub visited_contracts_num: usize,
     pub visited_funcs: Vec<String>,
@@ -26,34 +35,162 @@ pub struct Result {
     pub sensitive_callsigs: Vec<String>,
     pub overlap: Overlap,
     pub reentrancy_path_info: HashMap<String, PathInfo>,
+    pub unresolved_callees: Vec<UnresolvedCallee>,
+    /// External calls left out of the call graph, by contract address
+    pub unresolved_calls: BTreeMap<String, Vec<UnresolvedCall>>,
+    pub decompile_cache: CacheStats,
+    /// Contracts deployed by the analyzed contracts
+    pub created_contracts: Vec<CreatedContract>,
+    /// Analyzed contracts found to be standard proxies
+    pub proxies: Vec<ResolvedProxy>,
+    /// Findings of the creation and the runtime code, in a combined analysis
+    pub phases: Vec<PhaseFindings>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Findings of one phase of a combined creation and runtime analysis
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PhaseFindings {
+    pub analysis_loc: String,
+    pub is_attack: bool,
+    pub warning: String,
+    pub attack_matrix: HashMap<String, bool>,
+    pub semantic_features: SemanticFeatures,
+    pub external_call: ExternalCall,
+    pub sensitive_callsigs: Vec<String>,
+    pub overlap: Overlap,
+    pub reentrancy_path_info: HashMap<String, PathInfo>,
+}
+
+impl PhaseFindings {
+    pub fn of(result: &Result) -> Self {
+        PhaseFindings {
+            analysis_loc: result.analysis_loc.clone(),
+            is_attack: result.is_attack,
+            warning: result.warning.clone(),
+            attack_matrix: result.attack_matrix.clone(),
+            semantic_features: result.semantic_features.clone(),
+            external_call: result.external_call.clone(),
+            sensitive_callsigs: result.sensitive_callsigs.clone(),
+            overlap: result.overlap.clone(),
+            reentrancy_path_info: result.reentrancy_path_info.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SemanticFeatures {
     pub op_creation: OpCreation,
     pub op_selfdestruct: bool,
     pub op_env: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OpCreation {
     pub op_multicreate: bool,
     pub op_solecreate: bool,
 }
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ExternalCall {
     pub externalcall_inhook: bool,
     pub externalcall_infallback: bool,
     // pub hooks_focused: Vec<String>,
 }
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Overlap {
     pub has_overlap: bool,
     pub overlap_external_call: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PathInfo {
     pub victim_call: Vec<ReachableSiteInfo>,
     pub attacker_reenter: Vec<ReenterInfo>,
 }
+
+/// A contract deployed through CREATE or CREATE2 with init code embedded in
+/// the bytecode of its creator
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CreatedContract {
+    /// Address of the contract executing the creation
+    pub creator: String,
+    pub func_sign: String,
+    pub create_site: String,
+    /// CREATE or CREATE2
+    pub opcode: String,
+    /// Deployed address, unknown without the salt (CREATE2) or the nonce
+    /// of the creator (CREATE)
+    pub address: Option<String>,
+    pub salt: Option<String>,
+    /// Key of the init code; constructor arguments appended at run time
+    /// are not part of it
+    pub code_key: String,
+    /// Features of the constructor, when it was analyzed in the call graph
+    pub semantic_features: Option<SemanticFeatures>,
+}
+
+/// A proxy following a standard layout, with the contracts behind it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ResolvedProxy {
+    pub proxy: String,
+    pub layout: ProxyLayout,
+    /// Beacon returning the implementation of a beacon proxy
+    pub beacon: Option<String>,
+    pub implementation: String,
+}
+
+/// A callee whose code or storage-held address was not available offline
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UnresolvedCallee {
+    pub caller: String,
+    pub call_site: String,
+    pub target: String,
+    pub reason: String,
+}
+
+/// An external call whose target address or function selector is unknown
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct UnresolvedCall {
+    /// Function of the contract making the call
+    pub func_sign: String,
+    pub call_site: String,
+    pub call_op: String,
+    pub resolution: CalleeResolution,
+    pub reason: String,
+}
+
+/// Transaction the analysis is set in; unset addresses are unknown
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxContext {
+    /// EOA that signed the transaction
+    pub origin: Option<String>,
+    /// Caller of the entry function
+    pub sender: Option<String>,
+    /// Wei sent to the entry function
+    pub value: Option<U256>,
+}
+
+/// Decompilation cache usage of a run
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub evicted: usize,
+}
+
+/// A call path that returns to a contract on it, e.g. attacker -> victim ->
+/// attacker; the first and last node share the address
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CallCycle {
+    pub path: Vec<Node>,
+}
+
+/// Calls the call graph traversal did not follow
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Truncation {
+    pub max_depth: i32,
+    pub max_nodes: usize,
+    /// Calls deeper than `max_depth`
+    pub calls_beyond_depth: usize,
+    /// Calls dropped once a graph reached `max_nodes`
+    pub calls_beyond_budget: usize,
+}
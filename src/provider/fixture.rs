@@ -0,0 +1,167 @@
+//! Loading an on-disk JSON fixture into a [`MockProvider`], used for
+//! offline analysis
+//!
+//! The fixture maps account addresses to their state, with optional
+//! `transactions` and `receipts` lists in the JSON-RPC
+//! `eth_getTransactionByHash` and `eth_getTransactionReceipt` formats:
+//! `{"<addr>": {"code": "0x..", "creation_code": "0x..", "balance": "0x..",
+//! "storage": {"<hex slot>": "<word>"}, "calls": {"<call data>": "<return data>"}},
+//! "transactions": [..], "receipts": [..]}`
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+use web3::types::{Address, Transaction, TransactionReceipt, H256, U256};
+
+use crate::config::ConfigError;
+use crate::contract::bytecode::decode_hex;
+use crate::provider::MockProvider;
+
+#[derive(Deserialize)]
+struct RawFixture {
+    #[serde(default)]
+    transactions: Vec<Transaction>,
+    #[serde(default)]
+    receipts: Vec<TransactionReceipt>,
+    #[serde(flatten)]
+    accounts: HashMap<String, RawAccountState>,
+}
+
+#[derive(Deserialize)]
+struct RawAccountState {
+    #[serde(default)]
+    code: Option<String>,
+    #[serde(default)]
+    creation_code: Option<String>,
+    #[serde(default)]
+    balance: Option<String>,
+    #[serde(default)]
+    storage: HashMap<String, String>,
+    #[serde(default)]
+    calls: HashMap<String, String>,
+}
+
+impl MockProvider {
+    /// Provider holding the state of the fixture at `path`
+    pub fn from_fixture(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            ConfigError::OfflineInput(format!("cannot read {}: {}", path.display(), e))
+        })?;
+        Self::from_fixture_str(&content)
+    }
+
+    pub fn from_fixture_str(content: &str) -> Result<Self, ConfigError> {
+        let raw: RawFixture =
+            serde_json::from_str(content).map_err(|e| ConfigError::OfflineInput(e.to_string()))?;
+        let mut state = MockProvider::new();
+        for (addr, account) in raw.accounts {
+            let addr = Address::from_str(&addr).map_err(|e| {
+                ConfigError::OfflineInput(format!("invalid account address {}: {}", addr, e))
+            })?;
+            if let Some(code) = account.code {
+                state.set_code(addr, parse_code(&code)?);
+            }
+            if let Some(code) = account.creation_code {
+                state.set_creation_code(addr, parse_code(&code)?);
+            }
+            if let Some(balance) = account.balance {
+                state.set_balance(addr, parse_word(&balance)?);
+            }
+            for (slot, word) in account.storage {
+                let mut bytes = [0u8; 32];
+                parse_word(&word)?.to_big_endian(&mut bytes);
+                state.set_storage(addr, parse_word(&slot)?, H256::from(bytes));
+            }
+            for (data, output) in account.calls {
+                state.set_call_result(addr, parse_code(&data)?, parse_code(&output)?);
+            }
+        }
+        for transaction in raw.transactions {
+            state.add_transaction(transaction);
+        }
+        for receipt in raw.receipts {
+            state.add_receipt(receipt);
+        }
+        Ok(state)
+    }
+}
+
+fn parse_code(code: &str) -> Result<Vec<u8>, ConfigError> {
+    decode_hex(code).map_err(|e| ConfigError::OfflineInput(format!("invalid bytecode: {}", e)))
+}
+
+fn parse_word(word: &str) -> Result<U256, ConfigError> {
+    U256::from_str(word.trim_start_matches("0x"))
+        .map_err(|e| ConfigError::OfflineInput(format!("invalid word {}: {}", word, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LydiaError;
+    use crate::provider::StateProvider;
+
+    const FIXTURE: &str = r#"{
+        "0x10C509AA9ab291C76c45414e7CdBd375e1D5AcE8": {
+            "code": "0x6080",
+            "balance": "0xde0b6b3a7640000",
+            "storage": {
+                "0x0": "0x000000000000000000000000bb4cdb9cbd36b01bd1cbaebf2de08d9173bc095c",
+                "0x3": "0x01"
+            }
+        },
+        "transactions": [{
+            "hash": "0x00000000000000000000000000000000000000000000000000000000000000aa",
+            "nonce": "0x0",
+            "blockHash": null,
+            "blockNumber": null,
+            "transactionIndex": null,
+            "from": "0x10c509aa9ab291c76c45414e7cdbd375e1d5ace8",
+            "to": null,
+            "value": "0x0",
+            "gasPrice": "0x1",
+            "gas": "0x5208",
+            "input": "0x6080"
+        }]
+    }"#;
+
+    #[tokio::test]
+    async fn test_fixture_lookup() {
+        let fixture = MockProvider::from_fixture_str(FIXTURE).unwrap();
+        let addr = Address::from_str("0x10c509aa9ab291c76c45414e7cdbd375e1d5ace8").unwrap();
+        assert_eq!(
+            fixture.get_code(addr, None).await.unwrap(),
+            vec![0x60, 0x80]
+        );
+        assert_eq!(fixture.get_creation_code(addr).await.unwrap(), None);
+        assert_eq!(
+            fixture.get_balance(addr, None).await.unwrap(),
+            U256::exp10(18)
+        );
+        assert_eq!(
+            fixture
+                .get_storage(addr, U256::from(3), None)
+                .await
+                .unwrap(),
+            H256::from_low_u64_be(1)
+        );
+        assert!(matches!(
+            fixture.get_storage(addr, U256::from(1), None).await,
+            Err(LydiaError::StateUnavailable(_))
+        ));
+        let tx = fixture
+            .get_transaction(H256::from_low_u64_be(0xaa))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tx.input.0, vec![0x60, 0x80]);
+    }
+
+    #[test]
+    fn test_invalid_fixture() {
+        assert!(MockProvider::from_fixture_str(r#"{"0xnot-an-address": {}}"#).is_err());
+    }
+}
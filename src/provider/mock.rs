@@ -0,0 +1,250 @@
+//! In-memory provider whose state is set up programmatically, mainly for tests
+
+use std::collections::{HashMap, HashSet};
+
+use async_trait::async_trait;
+use web3::types::{Address, BlockNumber, Transaction, TransactionReceipt, H256, U256};
+
+use crate::error::{LydiaError, LydiaResult};
+use crate::provider::StateProvider;
+
+/// State of a single account known to a [`MockProvider`]
+#[derive(Debug, Default, Clone)]
+pub struct AccountState {
+    pub code: Option<Vec<u8>>,
+    pub creation_code: Option<Vec<u8>>,
+    pub balance: Option<U256>,
+    pub storage: HashMap<U256, H256>,
+    /// Return data of read-only calls, by call data
+    pub calls: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+/// Provider answering from in-memory accounts, transactions and receipts. Blocks are
+/// ignored: the state is a single snapshot.
+#[derive(Debug, Default, Clone)]
+pub struct MockProvider {
+    accounts: HashMap<Address, AccountState>,
+    transactions: HashMap<H256, Transaction>,
+    receipts: HashMap<H256, TransactionReceipt>,
+    /// Accounts whose reads fail as with every RPC endpoint down
+    unreachable: HashSet<Address>,
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_code(&mut self, addr: Address, code: Vec<u8>) {
+        self.account(addr).code = Some(code);
+    }
+
+    pub fn set_creation_code(&mut self, addr: Address, code: Vec<u8>) {
+        self.account(addr).creation_code = Some(code);
+    }
+
+    pub fn set_balance(&mut self, addr: Address, balance: U256) {
+        self.account(addr).balance = Some(balance);
+    }
+
+    pub fn set_storage(&mut self, addr: Address, slot: U256, word: H256) {
+        self.account(addr).storage.insert(slot, word);
+    }
+
+    pub fn set_call_result(&mut self, addr: Address, data: Vec<u8>, output: Vec<u8>) {
+        self.account(addr).calls.insert(data, output);
+    }
+
+    /// Make every read of `addr` fail with a network error
+    pub fn set_unreachable(&mut self, addr: Address) {
+        self.unreachable.insert(addr);
+    }
+
+    pub fn add_transaction(&mut self, transaction: Transaction) {
+        self.transactions.insert(transaction.hash, transaction);
+    }
+
+    pub fn add_receipt(&mut self, receipt: TransactionReceipt) {
+        self.receipts.insert(receipt.transaction_hash, receipt);
+    }
+
+    fn account(&mut self, addr: Address) -> &mut AccountState {
+        self.accounts.entry(addr).or_default()
+    }
+
+    fn reach(&self, addr: Address) -> LydiaResult<()> {
+        if self.unreachable.contains(&addr) {
+            return Err(LydiaError::Network(format!("{:?} is unreachable", addr)));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl StateProvider for MockProvider {
+    async fn get_code(
+        &self,
+        address: Address,
+        _block: Option<BlockNumber>,
+    ) -> LydiaResult<Vec<u8>> {
+        self.reach(address)?;
+        match self.accounts.get(&address) {
+            Some(AccountState {
+                code: Some(code), ..
+            }) => Ok(code.clone()),
+            // an account only known by its creation code is not deployed yet
+            Some(AccountState {
+                creation_code: Some(_),
+                ..
+            }) => Ok(Vec::new()),
+            _ => Err(LydiaError::StateUnavailable(format!(
+                "bytecode of {:?} not available",
+                address
+            ))),
+        }
+    }
+
+    async fn get_storage(
+        &self,
+        address: Address,
+        slot: U256,
+        _block: Option<BlockNumber>,
+    ) -> LydiaResult<H256> {
+        self.reach(address)?;
+        self.accounts
+            .get(&address)
+            .and_then(|account| account.storage.get(&slot))
+            .copied()
+            .ok_or_else(|| {
+                LydiaError::StateUnavailable(format!(
+                    "storage slot {:#x} of {:?} not available",
+                    slot, address
+                ))
+            })
+    }
+
+    async fn get_balance(
+        &self,
+        address: Address,
+        _block: Option<BlockNumber>,
+    ) -> LydiaResult<U256> {
+        self.reach(address)?;
+        self.accounts
+            .get(&address)
+            .and_then(|account| account.balance)
+            .ok_or_else(|| {
+                LydiaError::StateUnavailable(format!("balance of {:?} not available", address))
+            })
+    }
+
+    async fn call(
+        &self,
+        to: Address,
+        data: Vec<u8>,
+        _block: Option<BlockNumber>,
+    ) -> LydiaResult<Vec<u8>> {
+        self.reach(to)?;
+        self.accounts
+            .get(&to)
+            .and_then(|account| account.calls.get(&data))
+            .cloned()
+            .ok_or_else(|| {
+                LydiaError::StateUnavailable(format!(
+                    "call 0x{} to {:?} not available",
+                    hex::encode(&data),
+                    to
+                ))
+            })
+    }
+
+    async fn get_transaction(&self, hash: H256) -> LydiaResult<Option<Transaction>> {
+        self.transactions
+            .get(&hash)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| {
+                LydiaError::StateUnavailable(format!("transaction {:?} not available", hash))
+            })
+    }
+
+    async fn get_transaction_receipt(&self, hash: H256) -> LydiaResult<Option<TransactionReceipt>> {
+        self.receipts.get(&hash).cloned().map(Some).ok_or_else(|| {
+            LydiaError::StateUnavailable(format!("receipt of {:?} not available", hash))
+        })
+    }
+
+    async fn get_creation_code(&self, address: Address) -> LydiaResult<Option<Vec<u8>>> {
+        Ok(self
+            .accounts
+            .get(&address)
+            .and_then(|account| account.creation_code.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mock_provider() {
+        let addr = Address::from_low_u64_be(0xbeef);
+        let mut provider = MockProvider::new();
+        provider.set_code(addr, vec![0x60, 0x80]);
+        provider.set_storage(addr, U256::from(3), H256::from_low_u64_be(1));
+        provider.set_balance(addr, U256::exp10(18));
+
+        assert_eq!(
+            provider.get_code(addr, None).await.unwrap(),
+            vec![0x60, 0x80]
+        );
+        assert_eq!(
+            provider
+                .get_storage(addr, U256::from(3), None)
+                .await
+                .unwrap(),
+            H256::from_low_u64_be(1)
+        );
+        assert_eq!(
+            provider.get_balance(addr, None).await.unwrap(),
+            U256::exp10(18)
+        );
+        assert!(matches!(
+            provider.get_storage(addr, U256::from(4), None).await,
+            Err(LydiaError::StateUnavailable(_))
+        ));
+        provider.set_call_result(addr, vec![0x5c, 0x60, 0xda, 0x1b], vec![0x01]);
+        assert_eq!(
+            provider
+                .call(addr, vec![0x5c, 0x60, 0xda, 0x1b], None)
+                .await
+                .unwrap(),
+            vec![0x01]
+        );
+        assert!(matches!(
+            provider.call(addr, vec![0x00], None).await,
+            Err(LydiaError::StateUnavailable(_))
+        ));
+        assert!(matches!(
+            provider.get_code(Address::zero(), None).await,
+            Err(LydiaError::StateUnavailable(_))
+        ));
+        provider.set_unreachable(addr);
+        assert!(matches!(
+            provider.get_storage(addr, U256::from(3), None).await,
+            Err(LydiaError::Network(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_undeployed_account_has_empty_code() {
+        let addr = Address::from_low_u64_be(0xbeef);
+        let mut provider = MockProvider::new();
+        provider.set_creation_code(addr, vec![0x60, 0x80]);
+
+        assert!(provider.get_code(addr, None).await.unwrap().is_empty());
+        assert_eq!(
+            provider.get_creation_code(addr).await.unwrap(),
+            Some(vec![0x60, 0x80])
+        );
+    }
+}
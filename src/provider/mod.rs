@@ -0,0 +1,89 @@
+//! State providers for Lydia
+//!
+//! Every read of chain state (code, storage, balances and transactions) goes
+//! through a [`StateProvider`], so the same analysis can run against a live
+//! JSON-RPC endpoint, an on-disk fixture or an in-memory mock.
+
+pub mod fixture;
+pub mod mock;
+pub mod rpc;
+
+use std::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::info;
+use web3::types::{Address, BlockNumber, Transaction, TransactionReceipt, H256, U256};
+
+use crate::config::{Config, ConfigError};
+use crate::contract::bytecode::is_creation_bytecode;
+use crate::error::LydiaResult;
+
+pub use mock::MockProvider;
+pub use rpc::RpcProvider;
+
+/// Source of chain state read by the analysis.
+///
+/// Providers return [`LydiaError::StateUnavailable`](crate::LydiaError::StateUnavailable)
+/// when they cannot know a value (e.g. a fixture lacking an account), which
+/// the analysis reports as an unresolved callee instead of failing.
+#[async_trait]
+pub trait StateProvider: Send + Sync + fmt::Debug {
+    /// Runtime code of `address`, empty when no code is deployed
+    async fn get_code(&self, address: Address, block: Option<BlockNumber>) -> LydiaResult<Vec<u8>>;
+
+    async fn get_storage(
+        &self,
+        address: Address,
+        slot: U256,
+        block: Option<BlockNumber>,
+    ) -> LydiaResult<H256>;
+
+    async fn get_balance(&self, address: Address, block: Option<BlockNumber>) -> LydiaResult<U256>;
+
+    /// Return data of a read-only call of `to` with `data`
+    async fn call(
+        &self,
+        to: Address,
+        data: Vec<u8>,
+        block: Option<BlockNumber>,
+    ) -> LydiaResult<Vec<u8>>;
+
+    /// Transaction by hash, `None` when the provider knows it does not exist
+    async fn get_transaction(&self, hash: H256) -> LydiaResult<Option<Transaction>>;
+
+    /// Receipt of a mined transaction, `None` when the provider knows it
+    /// does not exist
+    async fn get_transaction_receipt(&self, hash: H256) -> LydiaResult<Option<TransactionReceipt>>;
+
+    /// Creation code of `address`, when the provider has it at hand
+    async fn get_creation_code(&self, _address: Address) -> LydiaResult<Option<Vec<u8>>> {
+        Ok(None)
+    }
+}
+
+/// Provider selected by the command line: a fixture holding the snapshot and
+/// local bytecode in offline mode, the platform's RPC endpoints otherwise
+pub async fn from_config(config: &Config) -> LydiaResult<Arc<dyn StateProvider>> {
+    if !config.offline {
+        return Ok(Arc::new(RpcProvider::connect(&config.chain()).await?));
+    }
+    let mut state = match &config.storage_snapshot {
+        Some(path) => MockProvider::from_fixture(path)?,
+        None => MockProvider::new(),
+    };
+    if let Some(code) = &config.bytecode {
+        let addr: Address = config
+            .logic_address
+            .parse()
+            .map_err(|_| ConfigError::ParseError("Invalid Ethereum address format".to_string()))?;
+        if config.createbin || is_creation_bytecode(code) {
+            info!("Analyzing {} as creation bytecode", config.logic_address);
+            state.set_code(addr, Vec::new());
+            state.set_creation_code(addr, code.clone());
+        } else {
+            state.set_code(addr, code.clone());
+        }
+    }
+    Ok(Arc::new(state))
+}
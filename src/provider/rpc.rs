@@ -0,0 +1,121 @@
+//! Provider reading live chain state over JSON-RPC
+
+use std::fmt;
+use std::time::Instant;
+
+use async_trait::async_trait;
+use log::{info, warn};
+use web3::types::{
+    Address, BlockNumber, Transaction, TransactionId, TransactionReceipt, H256, U256,
+};
+
+use crate::chain::ChainConfig;
+use crate::contract::status_fetcher::Web3Transport;
+use crate::error::{LydiaError, LydiaResult};
+use crate::provider::StateProvider;
+
+/// Run `$call` against each endpoint in order until one succeeds
+macro_rules! with_fallback {
+    ($self:ident, $transport:ident => $call:expr) => {{
+        let mut result = Err(LydiaError::Network(
+            "no RPC endpoint configured".to_string(),
+        ));
+        for (url, $transport) in &$self.endpoints {
+            match $call.await {
+                Ok(value) => {
+                    result = Ok(value);
+                    break;
+                }
+                Err(e) => {
+                    warn!("request to {} failed: {}", url, e);
+                    result = Err(LydiaError::Network(format!("{}: {}", url, e)));
+                }
+            }
+        }
+        result
+    }};
+}
+
+/// Provider backed by the endpoints of a chain; requests fall back from the
+/// primary URL to the fallback URLs when an endpoint fails
+pub struct RpcProvider {
+    endpoints: Vec<(String, Web3Transport)>,
+}
+
+impl RpcProvider {
+    /// Connect to every reachable endpoint of the chain
+    pub async fn connect(chain: &ChainConfig) -> LydiaResult<Self> {
+        let mut endpoints = Vec::new();
+        let mut last_error = "no RPC endpoint configured".to_string();
+        for url in chain.endpoints() {
+            match Web3Transport::new(url).await {
+                Ok(transport) => endpoints.push((url.to_string(), transport)),
+                Err(e) => {
+                    warn!("failed to connect to {}: {}", url, e);
+                    last_error = format!("{}: {}", url, e);
+                }
+            }
+        }
+        if endpoints.is_empty() {
+            return Err(LydiaError::Network(last_error));
+        }
+        Ok(Self { endpoints })
+    }
+}
+
+impl fmt::Debug for RpcProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RpcProvider")
+            .field(
+                "endpoints",
+                &self
+                    .endpoints
+                    .iter()
+                    .map(|(url, _)| url)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+#[async_trait]
+impl StateProvider for RpcProvider {
+    async fn get_code(&self, address: Address, block: Option<BlockNumber>) -> LydiaResult<Vec<u8>> {
+        let code = with_fallback!(self, transport => transport.get_code(address, block))?;
+        Ok(code.0)
+    }
+
+    async fn get_storage(
+        &self,
+        address: Address,
+        slot: U256,
+        block: Option<BlockNumber>,
+    ) -> LydiaResult<H256> {
+        let start = Instant::now();
+        let word = with_fallback!(self, transport => transport.get_storage(address, slot, block));
+        info!("read storage consumes {:?}", start.elapsed());
+        word
+    }
+
+    async fn get_balance(&self, address: Address, block: Option<BlockNumber>) -> LydiaResult<U256> {
+        with_fallback!(self, transport => transport.get_balance(address, block))
+    }
+
+    async fn call(
+        &self,
+        to: Address,
+        data: Vec<u8>,
+        block: Option<BlockNumber>,
+    ) -> LydiaResult<Vec<u8>> {
+        let output = with_fallback!(self, transport => transport.call(to, data.clone(), block))?;
+        Ok(output.0)
+    }
+
+    async fn get_transaction(&self, hash: H256) -> LydiaResult<Option<Transaction>> {
+        with_fallback!(self, transport => transport.get_transaction(TransactionId::Hash(hash)))
+    }
+
+    async fn get_transaction_receipt(&self, hash: H256) -> LydiaResult<Option<TransactionReceipt>> {
+        with_fallback!(self, transport => transport.get_transaction_receipt(hash))
+    }
+}
@@ -0,0 +1,150 @@
+//! Transaction-driven analysis
+//!
+//! `lydia tx <hash>` fetches the transaction and its receipt, and analyzes
+//! the contract it deployed or called at the block before its inclusion,
+//! with the transaction's sender and value as context.
+
+use log::{info, warn};
+use web3::types::{Address, H256, U256};
+
+use crate::config::Config;
+use crate::error::{LydiaError, LydiaResult};
+use crate::outputter::result_structure::TxContext;
+use crate::provider::StateProvider;
+
+/// The contract a transaction deployed or called, and the context to
+/// analyze it in
+#[derive(Debug, Clone, PartialEq)]
+pub struct TxTarget {
+    pub logic_address: Address,
+    /// Block before the one including the transaction
+    pub block_number: u64,
+    pub origin: Address,
+    pub value: U256,
+    /// Creation code, when the transaction deployed the contract
+    pub creation_code: Option<Vec<u8>>,
+}
+
+impl TxTarget {
+    /// Fetch transaction `hash` and its receipt from `provider`
+    pub async fn fetch(provider: &dyn StateProvider, hash: H256) -> LydiaResult<Self> {
+        let transaction = provider
+            .get_transaction(hash)
+            .await?
+            .ok_or_else(|| LydiaError::General(format!("transaction {:?} not found", hash)))?;
+        let receipt = provider
+            .get_transaction_receipt(hash)
+            .await?
+            .ok_or_else(|| {
+                LydiaError::General(format!("transaction {:?} is not mined yet", hash))
+            })?;
+        if receipt.status.is_some_and(|status| status.is_zero()) {
+            warn!("transaction {:?} reverted", hash);
+        }
+        let included = receipt
+            .block_number
+            .or(transaction.block_number)
+            .ok_or_else(|| LydiaError::General(format!("transaction {:?} is not mined yet", hash)))?
+            .as_u64();
+        let origin = transaction
+            .from
+            .ok_or_else(|| LydiaError::General(format!("transaction {:?} has no sender", hash)))?;
+
+        let (logic_address, creation_code) = match transaction.to {
+            Some(to) => (to, None),
+            None => {
+                let created = receipt.contract_address.ok_or_else(|| {
+                    LydiaError::General(format!("transaction {:?} created no contract", hash))
+                })?;
+                (created, Some(transaction.input.0))
+            }
+        };
+        Ok(TxTarget {
+            logic_address,
+            block_number: included.saturating_sub(1),
+            origin,
+            value: transaction.value,
+            creation_code,
+        })
+    }
+
+    /// Point `config` at the target. The creation code of a deployed
+    /// contract is stored for the createbin analysis, since the contract has
+    /// no code yet at the snapshot block.
+    pub fn apply(self, config: &mut Config) -> LydiaResult<()> {
+        let address = format!("{:?}", self.logic_address);
+        if let Some(code) = &self.creation_code {
+            info!(
+                "Transaction deploys {}, analyzing its creation bytecode",
+                address
+            );
+            config.workspace.write_createbin(&address, code)?;
+        }
+        let origin = format!("{:?}", self.origin);
+        config.logic_address = address.clone();
+        config.storage_address = address;
+        config.block_number = self.block_number;
+        config.tx = TxContext {
+            origin: Some(origin.clone()),
+            sender: Some(origin),
+            value: Some(self.value),
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider::MockProvider;
+    use web3::types::{Bytes, Transaction, TransactionReceipt, U64};
+
+    fn transaction(to: Option<Address>) -> (Transaction, TransactionReceipt) {
+        let hash = H256::from_low_u64_be(0xaa);
+        let transaction = Transaction {
+            hash,
+            from: Some(Address::from_low_u64_be(0xe0a)),
+            to,
+            value: U256::exp10(18),
+            input: Bytes(vec![0x60, 0x80]),
+            block_number: Some(U64::from(100)),
+            ..Transaction::default()
+        };
+        let receipt = TransactionReceipt {
+            transaction_hash: hash,
+            block_number: Some(U64::from(100)),
+            contract_address: to.is_none().then(|| Address::from_low_u64_be(0xc0de)),
+            status: Some(U64::from(1)),
+            ..TransactionReceipt::default()
+        };
+        (transaction, receipt)
+    }
+
+    async fn fetch(to: Option<Address>) -> LydiaResult<TxTarget> {
+        let (transaction, receipt) = transaction(to);
+        let mut provider = MockProvider::new();
+        provider.add_transaction(transaction);
+        provider.add_receipt(receipt);
+        TxTarget::fetch(&provider, H256::from_low_u64_be(0xaa)).await
+    }
+
+    #[tokio::test]
+    async fn test_fetch_called_contract() {
+        let target = fetch(Some(Address::from_low_u64_be(0xb0b))).await.unwrap();
+        assert_eq!(target.logic_address, Address::from_low_u64_be(0xb0b));
+        assert_eq!(target.block_number, 99);
+        assert_eq!(target.origin, Address::from_low_u64_be(0xe0a));
+        assert_eq!(target.value, U256::exp10(18));
+        assert_eq!(target.creation_code, None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_deployed_contract() {
+        let target = fetch(None).await.unwrap();
+        assert_eq!(target.logic_address, Address::from_low_u64_be(0xc0de));
+        assert_eq!(target.creation_code, Some(vec![0x60, 0x80]));
+
+        let missing = TxTarget::fetch(&MockProvider::new(), H256::zero()).await;
+        assert!(matches!(missing, Err(LydiaError::StateUnavailable(_))));
+    }
+}
@@ -8,6 +8,7 @@ use std::io::Write;
 
 use crate::config::defaults;
 use crate::error::LydiaResult;
+use crate::outputter::graph_renderer::{self, GraphFormat};
 use crate::outputter::result_structure::Result as AnalysisResult;
 
 /// Save analysis results to a JSON file
@@ -30,6 +31,17 @@ pub fn save_results_to_file(logic_address: &str, result: AnalysisResult) -> Lydi
     Ok(())
 }
 
+/// Save the call graph of the results next to them, in the given format
+pub fn save_graph_to_file(logic_address: &str, result: &AnalysisResult, format: GraphFormat) -> LydiaResult<()> {
+    let rendered = graph_renderer::render(result, format)?;
+    let file_path = format!("{}{}.{}", defaults::OUTPUT_DIR, logic_address, format.extension());
+    std::fs::create_dir_all(defaults::OUTPUT_DIR)?;
+    std::fs::write(&file_path, rendered)?;
+
+    println!("Call graph saved to: {}", file_path);
+    Ok(())
+}
+
 /// Initialize the logging system
 pub fn init_logging() {
     env_logger::init();
@@ -0,0 +1,385 @@
+//! Filesystem layout of a Lydia run
+//!
+//! The [`Workspace`] owns every path Lydia reads from or writes to around
+//! the Gigahorse toolchain: the toolchain itself, the bytecode files handed
+//! to it, its working directory holding the output relations, and the cache
+//! of compiled Datalog clients. The toolchain may live on a read-only prefix
+//! as long as the other directories are writable.
+//!
+//! Several Lydia processes may share one workspace. Bytecode files are named
+//! by the keccak hash of their content and written atomically, every run
+//! decompiles into its own scratch directory under the temp dir, and the
+//! compiled-client cache is only touched under a file lock.
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use web3::signing::keccak256;
+
+/// Environment variables overriding the default layout; the command line
+/// takes precedence over them
+pub mod env {
+    pub const TOOLCHAIN_DIR: &str = "LYDIA_GIGAHORSE_ROOT";
+    pub const CONTRACTS_DIR: &str = "LYDIA_CONTRACTS_DIR";
+    pub const TEMP_DIR: &str = "LYDIA_TEMP_DIR";
+    pub const CACHE_DIR: &str = "LYDIA_CACHE_DIR";
+    pub const DECOMPILED_DIR: &str = "LYDIA_DECOMPILE_CACHE";
+}
+
+pub const DEFAULT_TOOLCHAIN_DIR: &str = "./gigahorse-toolchain";
+/// Name of the client analysis, prefixing every output relation file
+pub const DEFAULT_ANALYSIS: &str = "Leslie";
+
+/// Distinguishes staging files of concurrent writers within one process
+static STAGING_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Workspace {
+    /// Gigahorse checkout containing `gigahorse.py` and the clients
+    pub toolchain_dir: PathBuf,
+    /// Bytecode files (`<code key>.hex`) and the `createbin/` folder
+    pub contracts_dir: PathBuf,
+    /// Parent of the per-run scratch directories
+    pub temp_dir: PathBuf,
+    /// Gigahorse working directory of this run, one `<code key>/out/` per
+    /// contract; equal to `temp_dir` until [`Workspace::isolate`] is called
+    pub scratch_dir: PathBuf,
+    /// Compiled Datalog clients
+    pub cache_dir: PathBuf,
+    /// Persistent Leslie relations of every decompiled code, one
+    /// `<code key>/out/` per code, shared by all runs
+    pub decompiled_dir: PathBuf,
+    pub analysis: String,
+}
+
+impl Default for Workspace {
+    fn default() -> Self {
+        Self::new(PathBuf::from(DEFAULT_TOOLCHAIN_DIR))
+    }
+}
+
+impl Workspace {
+    /// Layout keeping everything inside the toolchain checkout
+    pub fn new(toolchain_dir: PathBuf) -> Self {
+        Self {
+            contracts_dir: toolchain_dir.join("contracts"),
+            temp_dir: toolchain_dir.join(".temp"),
+            scratch_dir: toolchain_dir.join(".temp"),
+            cache_dir: toolchain_dir.join("cache"),
+            decompiled_dir: toolchain_dir.join(".temp").join("decompiled"),
+            toolchain_dir,
+            analysis: DEFAULT_ANALYSIS.to_string(),
+        }
+    }
+
+    /// Create the writable directories and make every path absolute, so the
+    /// layout stays valid whatever directory the toolchain is run from
+    pub fn prepare(&self) -> io::Result<Self> {
+        fs::create_dir_all(&self.contracts_dir)?;
+        fs::create_dir_all(&self.temp_dir)?;
+        fs::create_dir_all(&self.cache_dir)?;
+        fs::create_dir_all(&self.decompiled_dir)?;
+        Ok(Self {
+            toolchain_dir: std::path::absolute(&self.toolchain_dir)?,
+            contracts_dir: std::path::absolute(&self.contracts_dir)?,
+            temp_dir: std::path::absolute(&self.temp_dir)?,
+            scratch_dir: std::path::absolute(&self.scratch_dir)?,
+            cache_dir: std::path::absolute(&self.cache_dir)?,
+            decompiled_dir: std::path::absolute(&self.decompiled_dir)?,
+            analysis: self.analysis.clone(),
+        })
+    }
+
+    /// Give this run a scratch directory of its own under the temp dir
+    pub fn isolate(&self) -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos())
+            .unwrap_or_default();
+        let scratch_dir = self
+            .temp_dir
+            .join(format!("run-{}-{}", std::process::id(), nanos));
+        fs::create_dir_all(&scratch_dir)?;
+        Ok(Self {
+            scratch_dir,
+            ..self.clone()
+        })
+    }
+
+    /// Remove the scratch directory of an isolated run
+    pub fn cleanup(&self) -> io::Result<()> {
+        if self.scratch_dir != self.temp_dir && self.scratch_dir.exists() {
+            fs::remove_dir_all(&self.scratch_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Content-addressed key of a bytecode: its keccak hash in hex
+    pub fn code_key(code: &[u8]) -> String {
+        hex::encode(keccak256(code))
+    }
+
+    /// Store `code` as `<code key>.hex` unless already present and return the
+    /// key. The file is renamed into place, so concurrent writers of the
+    /// same code never expose a partial file.
+    pub fn write_bytecode(&self, code: &[u8]) -> io::Result<String> {
+        let code_key = Self::code_key(code);
+        let path = self.bytecode_path(&code_key);
+        if !path.exists() {
+            let staging = self.contracts_dir.join(format!(
+                ".{}.{}.{}.tmp",
+                code_key,
+                std::process::id(),
+                STAGING_SEQ.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::write(&staging, hex::encode(code))?;
+            fs::rename(&staging, &path)?;
+        }
+        Ok(code_key)
+    }
+
+    pub fn bytecode_path(&self, code_key: &str) -> PathBuf {
+        self.contracts_dir.join(format!("{}.hex", code_key))
+    }
+
+    /// Pre-populated creation code of a contract without runtime code
+    pub fn createbin_path(&self, addr: &str) -> PathBuf {
+        self.contracts_dir
+            .join("createbin")
+            .join(format!("{}_createbin.hex", addr))
+    }
+
+    /// Store `code` as the creation code of `addr`, e.g. taken from its
+    /// deployment transaction
+    pub fn write_createbin(&self, addr: &str, code: &[u8]) -> io::Result<()> {
+        let path = self.createbin_path(addr);
+        let dir = self.contracts_dir.join("createbin");
+        fs::create_dir_all(&dir)?;
+        let staging = dir.join(format!(
+            ".{}.{}.{}.tmp",
+            addr,
+            std::process::id(),
+            STAGING_SEQ.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&staging, hex::encode(code))?;
+        fs::rename(&staging, &path)
+    }
+
+    /// Output relations of the code: the persistent copy when the code was
+    /// decompiled before, this run's scratch output otherwise
+    pub fn out_dir(&self, code_key: &str) -> PathBuf {
+        let decompiled = self.decompiled_out_dir(code_key);
+        if decompiled.is_dir() {
+            decompiled
+        } else {
+            self.scratch_out_dir(code_key)
+        }
+    }
+
+    /// Where Gigahorse writes the relations of the code during this run
+    pub fn scratch_out_dir(&self, code_key: &str) -> PathBuf {
+        self.scratch_dir.join(code_key).join("out")
+    }
+
+    pub fn decompiled_out_dir(&self, code_key: &str) -> PathBuf {
+        self.decompiled_dir.join(code_key).join("out")
+    }
+
+    /// Output relation `<analysis>_<relation>.csv` of the contract analyzed
+    /// under `code_key`
+    pub fn relation_path(&self, code_key: &str, relation: &str) -> PathBuf {
+        self.out_dir(code_key)
+            .join(format!("{}_{}.csv", self.analysis, relation))
+    }
+
+    /// Gigahorse results file of one bytecode file
+    pub fn results_path(&self, contract_name: &str) -> PathBuf {
+        self.scratch_dir
+            .join(format!("{}.results.json", contract_name))
+    }
+
+    /// Private compiled-client cache of one Gigahorse invocation
+    pub fn private_cache_dir(&self, code_key: &str) -> PathBuf {
+        self.scratch_dir.join(".cache").join(code_key)
+    }
+
+    /// Fill `private_dir` with the compiled clients of the shared cache.
+    ///
+    /// Gigahorse overwrites the client executables in its cache dir on every
+    /// start, so concurrent invocations must not share one. The compiled
+    /// programs themselves (named by the hash of their source) are
+    /// immutable and hard-linked in under a shared lock.
+    pub fn seed_private_cache(&self, private_dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(private_dir)?;
+        let _lock = self.lock_cache(false)?;
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if !is_compiled_program(&name.to_string_lossy()) {
+                continue;
+            }
+            let target = private_dir.join(&name);
+            if !target.exists() && fs::hard_link(entry.path(), &target).is_err() {
+                fs::copy(entry.path(), &target)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move programs compiled during an invocation into the shared cache
+    /// under an exclusive lock
+    pub fn publish_private_cache(&self, private_dir: &Path) -> io::Result<()> {
+        let _lock = self.lock_cache(true)?;
+        for entry in fs::read_dir(private_dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let target = self.cache_dir.join(&name);
+            if is_compiled_program(&name.to_string_lossy()) && !target.exists() {
+                let staging = self.cache_dir.join(format!(
+                    ".{}.{}.tmp",
+                    name.to_string_lossy(),
+                    std::process::id()
+                ));
+                fs::copy(entry.path(), &staging)?;
+                fs::rename(&staging, &target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn lock_cache(&self, exclusive: bool) -> io::Result<File> {
+        lock_dir(&self.cache_dir, exclusive)
+    }
+}
+
+/// Lock file of a directory shared between processes; released when dropped
+pub(crate) fn lock_dir(dir: &Path, exclusive: bool) -> io::Result<File> {
+    fs::create_dir_all(dir)?;
+    let lock = File::create(dir.join(".lock"))?;
+    if exclusive {
+        lock.lock()?;
+    } else {
+        lock.lock_shared()?;
+    }
+    Ok(lock)
+}
+
+/// Gigahorse stores compiled programs under the md5 hash of their source
+fn is_compiled_program(name: &str) -> bool {
+    name.len() == 32 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Workspace laid out under a directory of its own in the system temp dir,
+/// removed with everything in it when dropped
+#[cfg(test)]
+pub(crate) struct TestWorkspace {
+    pub root: PathBuf,
+    pub workspace: Workspace,
+}
+
+#[cfg(test)]
+impl TestWorkspace {
+    /// `name` keeps the directories of tests running in parallel apart
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("lydia-{}-{}", name, std::process::id()));
+        // left over by an earlier run of the same process id
+        let _ = fs::remove_dir_all(&root);
+        let mut workspace = Workspace::new(root.clone());
+        workspace.temp_dir = root.join("temp");
+        workspace.scratch_dir = root.join("temp");
+        workspace.decompiled_dir = root.join("decompiled");
+        Self { root, workspace }
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestWorkspace {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_default_layout() {
+        let workspace = Workspace::default();
+        assert_eq!(
+            workspace.bytecode_path("0xab_1"),
+            Path::new("./gigahorse-toolchain/contracts/0xab_1.hex")
+        );
+        assert_eq!(
+            workspace.relation_path("0xab_1", "FunctionSelector"),
+            Path::new("./gigahorse-toolchain/.temp/0xab_1/out/Leslie_FunctionSelector.csv")
+        );
+    }
+
+    #[test]
+    fn test_prepare_creates_scratch_dirs() {
+        let test = TestWorkspace::new("workspace");
+        let root = &test.root;
+        assert!(!test.workspace.temp_dir.exists());
+
+        let prepared = test.workspace.prepare().unwrap();
+        assert!(prepared.contracts_dir.is_dir() && prepared.temp_dir.is_dir());
+        assert!(prepared.temp_dir.is_absolute());
+        assert_eq!(
+            prepared.out_dir("k"),
+            root.join("temp").join("k").join("out")
+        );
+
+        // two runs never share a scratch directory
+        let first = prepared.isolate().unwrap();
+        let second = prepared.isolate().unwrap();
+        assert_ne!(first.out_dir("k"), second.out_dir("k"));
+        assert!(first.scratch_dir.starts_with(&prepared.temp_dir));
+        first.cleanup().unwrap();
+        assert!(!first.scratch_dir.exists() && second.scratch_dir.exists());
+        prepared.cleanup().unwrap();
+        assert!(prepared.temp_dir.exists());
+    }
+
+    #[test]
+    fn test_content_addressed_bytecode() {
+        let test = TestWorkspace::new("bytecode");
+        let workspace = &test.workspace;
+        fs::create_dir_all(&workspace.contracts_dir).unwrap();
+
+        let key = workspace.write_bytecode(&[0x60, 0x80]).unwrap();
+        assert_eq!(key, Workspace::code_key(&[0x60, 0x80]));
+        assert_eq!(workspace.write_bytecode(&[0x60, 0x80]).unwrap(), key);
+        assert_ne!(workspace.write_bytecode(&[0x60, 0x60]).unwrap(), key);
+        assert_eq!(
+            fs::read_to_string(workspace.bytecode_path(&key)).unwrap(),
+            "6080"
+        );
+    }
+
+    #[test]
+    fn test_private_cache_round_trip() {
+        let test = TestWorkspace::new("cache");
+        let workspace = &test.workspace;
+        fs::create_dir_all(&workspace.cache_dir).unwrap();
+        let compiled = "0123456789abcdef0123456789abcdef";
+        fs::write(workspace.cache_dir.join(compiled), "bin").unwrap();
+
+        let private = test.root.join("private");
+        workspace.seed_private_cache(&private).unwrap();
+        assert!(private.join(compiled).exists());
+        assert!(!private.join(".lock").exists());
+
+        // executables copied by Gigahorse stay private, new programs are shared
+        let fresh = "fedcba9876543210fedcba9876543210";
+        fs::write(private.join("leslie.dl_compiled"), "exe").unwrap();
+        fs::write(private.join(fresh), "bin").unwrap();
+        workspace.publish_private_cache(&private).unwrap();
+        assert!(workspace.cache_dir.join(fresh).exists());
+        assert!(!workspace.cache_dir.join("leslie.dl_compiled").exists());
+    }
+}